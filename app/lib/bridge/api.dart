@@ -6,20 +6,28 @@
 import 'frb_generated.dart';
 import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
 
-// These function are ignored because they are on traits that is not defined in current crate (put an empty `#[frb]` on it to unignore): `clone`, `fmt`, `from`
+// These function are ignored because they are on traits that is not defined in current crate (put an empty `#[frb]` on it to unignore): `clone`, `default`, `fmt`, `from`, `try_from`
 
 Future<ArcMutexP2PEngine> getEngine() =>
     P2PBridge.instance.api.crateApiGetEngine();
 
+/// Install a global log subscriber at `level` (e.g. "info", "p2p_core=debug"), call at most once
+Future<void> initLogging({required String level}) =>
+    P2PBridge.instance.api.crateApiInitLogging(level: level);
+
 Future<String> initP2PEngine() =>
     P2PBridge.instance.api.crateApiInitP2PEngine();
 
 Future<void> startPeerDiscovery({
   required String deviceName,
   required String deviceType,
+  required int? port,
+  required Map<String, String> properties,
 }) => P2PBridge.instance.api.crateApiStartPeerDiscovery(
   deviceName: deviceName,
   deviceType: deviceType,
+  port: port,
+  properties: properties,
 );
 
 Future<void> stopPeerDiscovery() =>
@@ -28,6 +36,9 @@ Future<void> stopPeerDiscovery() =>
 Future<List<FlutterPeer>> getDiscoveredPeers() =>
     P2PBridge.instance.api.crateApiGetDiscoveredPeers();
 
+Future<List<FlutterInterface>> listNetworkInterfaces() =>
+    P2PBridge.instance.api.crateApiListNetworkInterfaces();
+
 // Rust type: RustOpaqueMoi<flutter_rust_bridge::for_generated::RustAutoOpaqueInner<Arc < Mutex < P2PEngine > >>>
 abstract class ArcMutexP2PEngine implements RustOpaqueInterface {}
 
@@ -41,32 +52,113 @@ abstract class P2PEngine implements RustOpaqueInterface {
 
   Future<String> getVersion();
 
+  Future<bool> isDiscoveryActive();
+
+  /// Every local interface address, loopback included so the UI can decide what to show
+  ///
+  /// Empty when the interfaces can't be enumerated, the failure is logged.
+  Future<List<FlutterInterface>> listInterfaces();
+
+  /// Create an engine, logging is left to the host app or `init_logging`
   // HINT: Make it `#[frb(sync)]` to let it become the default constructor of Dart class.
   static Future<P2PEngine> newInstance() =>
       P2PBridge.instance.api.crateApiP2PEngineNew();
 
+  /// Ask the network for fresh details of one peer, `None` when it isn't known
+  Future<FlutterPeer?> refreshPeer({required String id});
+
+  /// Start discovery, collect peers for `timeout_seconds` and stop again, e.g. for pull-to-refresh
+  ///
+  /// An already running discovery is reused and left running.
+  Future<List<FlutterPeer>> scanOnce({
+    required String deviceName,
+    required String deviceType,
+    required BigInt timeoutSeconds,
+  });
+
+  /// Start advertising and browsing, `port` defaults to 8080 when not provided
   Future<void> startDiscovery({
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   });
 
   Future<void> stopDiscovery();
 }
 
+class FlutterError implements FrbException {
+  final String code;
+  final String message;
+
+  const FlutterError({required this.code, required this.message});
+
+  @override
+  int get hashCode => code.hashCode ^ message.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is FlutterError &&
+          runtimeType == other.runtimeType &&
+          code == other.code &&
+          message == other.message;
+}
+
+class FlutterInterface {
+  final String name;
+  final String ip;
+  final bool isLoopback;
+
+  const FlutterInterface({
+    required this.name,
+    required this.ip,
+    required this.isLoopback,
+  });
+
+  @override
+  int get hashCode => name.hashCode ^ ip.hashCode ^ isLoopback.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is FlutterInterface &&
+          runtimeType == other.runtimeType &&
+          name == other.name &&
+          ip == other.ip &&
+          isLoopback == other.isLoopback;
+}
+
 class FlutterPeer {
   final String id;
   final String name;
+  final String hostname;
   final String ip;
+  final List<String> addresses;
+  /// No usable address yet, `ip` is "0.0.0.0" until one resolves
+  final bool addressPending;
   final int port;
+  /// e.g. "_qopyapp._tcp.local.", needed to turn the peer back into a core `Peer`
+  final String serviceType;
   final String deviceType;
+  /// "fresh", "recent" or "stale", see `Freshness`
+  final String freshness;
+  /// False when the peer's protocol version is below the supported minimum
+  final bool compatible;
   final Map<String, String> properties;
 
   const FlutterPeer({
     required this.id,
     required this.name,
+    required this.hostname,
     required this.ip,
+    required this.addresses,
+    required this.addressPending,
     required this.port,
+    required this.serviceType,
     required this.deviceType,
+    required this.freshness,
+    required this.compatible,
     required this.properties,
   });
 
@@ -74,9 +166,15 @@ class FlutterPeer {
   int get hashCode =>
       id.hashCode ^
       name.hashCode ^
+      hostname.hashCode ^
       ip.hashCode ^
+      addresses.hashCode ^
+      addressPending.hashCode ^
       port.hashCode ^
+      serviceType.hashCode ^
       deviceType.hashCode ^
+      freshness.hashCode ^
+      compatible.hashCode ^
       properties.hashCode;
 
   @override
@@ -86,8 +184,14 @@ class FlutterPeer {
           runtimeType == other.runtimeType &&
           id == other.id &&
           name == other.name &&
+          hostname == other.hostname &&
           ip == other.ip &&
+          addresses == other.addresses &&
+          addressPending == other.addressPending &&
           port == other.port &&
+          serviceType == other.serviceType &&
           deviceType == other.deviceType &&
+          freshness == other.freshness &&
+          compatible == other.compatible &&
           properties == other.properties;
 }
@@ -65,7 +65,7 @@ class P2PBridge
   String get codegenVersion => '2.11.1';
 
   @override
-  int get rustContentHash => 475141219;
+  int get rustContentHash => -1804626358;
 
   static const kDefaultExternalLibraryLoaderConfig =
       ExternalLibraryLoaderConfig(
@@ -87,12 +87,32 @@ abstract class P2PBridgeApi extends BaseApi {
 
   Future<String> crateApiP2PEngineGetVersion({required P2PEngine that});
 
+  Future<bool> crateApiP2PEngineIsDiscoveryActive({required P2PEngine that});
+
+  Future<List<FlutterInterface>> crateApiP2PEngineListInterfaces({
+    required P2PEngine that,
+  });
+
   Future<P2PEngine> crateApiP2PEngineNew();
 
+  Future<FlutterPeer?> crateApiP2PEngineRefreshPeer({
+    required P2PEngine that,
+    required String id,
+  });
+
+  Future<List<FlutterPeer>> crateApiP2PEngineScanOnce({
+    required P2PEngine that,
+    required String deviceName,
+    required String deviceType,
+    required BigInt timeoutSeconds,
+  });
+
   Future<void> crateApiP2PEngineStartDiscovery({
     required P2PEngine that,
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   });
 
   Future<void> crateApiP2PEngineStopDiscovery({required P2PEngine that});
@@ -101,11 +121,17 @@ abstract class P2PBridgeApi extends BaseApi {
 
   Future<ArcMutexP2PEngine> crateApiGetEngine();
 
+  Future<void> crateApiInitLogging({required String level});
+
   Future<String> crateApiInitP2PEngine();
 
+  Future<List<FlutterInterface>> crateApiListNetworkInterfaces();
+
   Future<void> crateApiStartPeerDiscovery({
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   });
 
   Future<void> crateApiStopPeerDiscovery();
@@ -160,7 +186,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_list_flutter_peer,
-          decodeErrorData: sse_decode_String,
+          decodeErrorData: sse_decode_flutter_error,
         ),
         constMeta: kCrateApiP2PEngineDiscoverPeersWithTimeoutConstMeta,
         argValues: [that, timeoutSeconds],
@@ -243,11 +269,15 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
       );
 
   @override
-  Future<P2PEngine> crateApiP2PEngineNew() {
+  Future<bool> crateApiP2PEngineIsDiscoveryActive({required P2PEngine that}) {
     return handler.executeNormal(
       NormalTask(
         callFfi: (port_) {
           final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_Auto_Ref_RustOpaque_flutter_rust_bridgefor_generatedRustAutoOpaqueInnerP2PEngine(
+            that,
+            serializer,
+          );
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
@@ -255,6 +285,72 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
             port: port_,
           );
         },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_bool,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateApiP2PEngineIsDiscoveryActiveConstMeta,
+        argValues: [that],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiP2PEngineIsDiscoveryActiveConstMeta =>
+      const TaskConstMeta(
+        debugName: "P2PEngine_is_discovery_active",
+        argNames: ["that"],
+      );
+
+  @override
+  Future<List<FlutterInterface>> crateApiP2PEngineListInterfaces({
+    required P2PEngine that,
+  }) {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_Auto_Ref_RustOpaque_flutter_rust_bridgefor_generatedRustAutoOpaqueInnerP2PEngine(
+            that,
+            serializer,
+          );
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 5,
+            port: port_,
+          );
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_list_flutter_interface,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateApiP2PEngineListInterfacesConstMeta,
+        argValues: [that],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiP2PEngineListInterfacesConstMeta =>
+      const TaskConstMeta(
+        debugName: "P2PEngine_list_interfaces",
+        argNames: ["that"],
+      );
+
+  @override
+  Future<P2PEngine> crateApiP2PEngineNew() {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 6,
+            port: port_,
+          );
+        },
         codec: SseCodec(
           decodeSuccessData:
               sse_decode_Auto_Owned_RustOpaque_flutter_rust_bridgefor_generatedRustAutoOpaqueInnerP2PEngine,
@@ -270,11 +366,92 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
   TaskConstMeta get kCrateApiP2PEngineNewConstMeta =>
       const TaskConstMeta(debugName: "P2PEngine_new", argNames: []);
 
+  @override
+  Future<FlutterPeer?> crateApiP2PEngineRefreshPeer({
+    required P2PEngine that,
+    required String id,
+  }) {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_Auto_Ref_RustOpaque_flutter_rust_bridgefor_generatedRustAutoOpaqueInnerP2PEngine(
+            that,
+            serializer,
+          );
+          sse_encode_String(id, serializer);
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 7,
+            port: port_,
+          );
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_opt_box_autoadd_flutter_peer,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateApiP2PEngineRefreshPeerConstMeta,
+        argValues: [that, id],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiP2PEngineRefreshPeerConstMeta =>
+      const TaskConstMeta(
+        debugName: "P2PEngine_refresh_peer",
+        argNames: ["that", "id"],
+      );
+
+  @override
+  Future<List<FlutterPeer>> crateApiP2PEngineScanOnce({
+    required P2PEngine that,
+    required String deviceName,
+    required String deviceType,
+    required BigInt timeoutSeconds,
+  }) {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_Auto_RefMut_RustOpaque_flutter_rust_bridgefor_generatedRustAutoOpaqueInnerP2PEngine(
+            that,
+            serializer,
+          );
+          sse_encode_String(deviceName, serializer);
+          sse_encode_String(deviceType, serializer);
+          sse_encode_u_64(timeoutSeconds, serializer);
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 8,
+            port: port_,
+          );
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_list_flutter_peer,
+          decodeErrorData: sse_decode_flutter_error,
+        ),
+        constMeta: kCrateApiP2PEngineScanOnceConstMeta,
+        argValues: [that, deviceName, deviceType, timeoutSeconds],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiP2PEngineScanOnceConstMeta => const TaskConstMeta(
+    debugName: "P2PEngine_scan_once",
+    argNames: ["that", "deviceName", "deviceType", "timeoutSeconds"],
+  );
+
   @override
   Future<void> crateApiP2PEngineStartDiscovery({
     required P2PEngine that,
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   }) {
     return handler.executeNormal(
       NormalTask(
@@ -286,19 +463,21 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           );
           sse_encode_String(deviceName, serializer);
           sse_encode_String(deviceType, serializer);
+          sse_encode_opt_box_autoadd_u_16(port, serializer);
+          sse_encode_Map_String_String_None(properties, serializer);
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 5,
+            funcId: 9,
             port: port_,
           );
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
-          decodeErrorData: sse_decode_String,
+          decodeErrorData: sse_decode_flutter_error,
         ),
         constMeta: kCrateApiP2PEngineStartDiscoveryConstMeta,
-        argValues: [that, deviceName, deviceType],
+        argValues: [that, deviceName, deviceType, port, properties],
         apiImpl: this,
       ),
     );
@@ -307,7 +486,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
   TaskConstMeta get kCrateApiP2PEngineStartDiscoveryConstMeta =>
       const TaskConstMeta(
         debugName: "P2PEngine_start_discovery",
-        argNames: ["that", "deviceName", "deviceType"],
+        argNames: ["that", "deviceName", "deviceType", "port", "properties"],
       );
 
   @override
@@ -323,13 +502,13 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 6,
+            funcId: 10,
             port: port_,
           );
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
-          decodeErrorData: sse_decode_String,
+          decodeErrorData: sse_decode_flutter_error,
         ),
         constMeta: kCrateApiP2PEngineStopDiscoveryConstMeta,
         argValues: [that],
@@ -353,7 +532,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 7,
+            funcId: 11,
             port: port_,
           );
         },
@@ -380,7 +559,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 8,
+            funcId: 12,
             port: port_,
           );
         },
@@ -399,6 +578,34 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
   TaskConstMeta get kCrateApiGetEngineConstMeta =>
       const TaskConstMeta(debugName: "get_engine", argNames: []);
 
+  @override
+  Future<void> crateApiInitLogging({required String level}) {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(level, serializer);
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 13,
+            port: port_,
+          );
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: sse_decode_flutter_error,
+        ),
+        constMeta: kCrateApiInitLoggingConstMeta,
+        argValues: [level],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiInitLoggingConstMeta =>
+      const TaskConstMeta(debugName: "init_logging", argNames: ["level"]);
+
   @override
   Future<String> crateApiInitP2PEngine() {
     return handler.executeNormal(
@@ -408,7 +615,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 9,
+            funcId: 14,
             port: port_,
           );
         },
@@ -426,10 +633,39 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
   TaskConstMeta get kCrateApiInitP2PEngineConstMeta =>
       const TaskConstMeta(debugName: "init_p2p_engine", argNames: []);
 
+  @override
+  Future<List<FlutterInterface>> crateApiListNetworkInterfaces() {
+    return handler.executeNormal(
+      NormalTask(
+        callFfi: (port_) {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          pdeCallFfi(
+            generalizedFrbRustBinding,
+            serializer,
+            funcId: 15,
+            port: port_,
+          );
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_list_flutter_interface,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateApiListNetworkInterfacesConstMeta,
+        argValues: [],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateApiListNetworkInterfacesConstMeta =>
+      const TaskConstMeta(debugName: "list_network_interfaces", argNames: []);
+
   @override
   Future<void> crateApiStartPeerDiscovery({
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   }) {
     return handler.executeNormal(
       NormalTask(
@@ -437,19 +673,21 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           final serializer = SseSerializer(generalizedFrbRustBinding);
           sse_encode_String(deviceName, serializer);
           sse_encode_String(deviceType, serializer);
+          sse_encode_opt_box_autoadd_u_16(port, serializer);
+          sse_encode_Map_String_String_None(properties, serializer);
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 10,
+            funcId: 16,
             port: port_,
           );
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
-          decodeErrorData: sse_decode_String,
+          decodeErrorData: sse_decode_flutter_error,
         ),
         constMeta: kCrateApiStartPeerDiscoveryConstMeta,
-        argValues: [deviceName, deviceType],
+        argValues: [deviceName, deviceType, port, properties],
         apiImpl: this,
       ),
     );
@@ -457,7 +695,7 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
 
   TaskConstMeta get kCrateApiStartPeerDiscoveryConstMeta => const TaskConstMeta(
     debugName: "start_peer_discovery",
-    argNames: ["deviceName", "deviceType"],
+    argNames: ["deviceName", "deviceType", "port", "properties"],
   );
 
   @override
@@ -469,13 +707,13 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
           pdeCallFfi(
             generalizedFrbRustBinding,
             serializer,
-            funcId: 11,
+            funcId: 17,
             port: port_,
           );
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
-          decodeErrorData: sse_decode_String,
+          decodeErrorData: sse_decode_flutter_error,
         ),
         constMeta: kCrateApiStopPeerDiscoveryConstMeta,
         argValues: [],
@@ -573,22 +811,83 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     return raw as String;
   }
 
+  @protected
+  bool dco_decode_bool(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as bool;
+  }
+
+  @protected
+  FlutterPeer dco_decode_box_autoadd_flutter_peer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_flutter_peer(raw);
+  }
+
+  @protected
+  int dco_decode_box_autoadd_u_16(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as int;
+  }
+
+  @protected
+  FlutterError dco_decode_flutter_error(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 2)
+      throw Exception('unexpected arr length: expect 2 but see ${arr.length}');
+    return FlutterError(
+      code: dco_decode_String(arr[0]),
+      message: dco_decode_String(arr[1]),
+    );
+  }
+
+  @protected
+  FlutterInterface dco_decode_flutter_interface(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 3)
+      throw Exception('unexpected arr length: expect 3 but see ${arr.length}');
+    return FlutterInterface(
+      name: dco_decode_String(arr[0]),
+      ip: dco_decode_String(arr[1]),
+      isLoopback: dco_decode_bool(arr[2]),
+    );
+  }
+
   @protected
   FlutterPeer dco_decode_flutter_peer(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
     final arr = raw as List<dynamic>;
-    if (arr.length != 6)
-      throw Exception('unexpected arr length: expect 6 but see ${arr.length}');
+    if (arr.length != 12)
+      throw Exception('unexpected arr length: expect 12 but see ${arr.length}');
     return FlutterPeer(
       id: dco_decode_String(arr[0]),
       name: dco_decode_String(arr[1]),
-      ip: dco_decode_String(arr[2]),
-      port: dco_decode_u_16(arr[3]),
-      deviceType: dco_decode_String(arr[4]),
-      properties: dco_decode_Map_String_String_None(arr[5]),
+      hostname: dco_decode_String(arr[2]),
+      ip: dco_decode_String(arr[3]),
+      addresses: dco_decode_list_String(arr[4]),
+      addressPending: dco_decode_bool(arr[5]),
+      port: dco_decode_u_16(arr[6]),
+      serviceType: dco_decode_String(arr[7]),
+      deviceType: dco_decode_String(arr[8]),
+      freshness: dco_decode_String(arr[9]),
+      compatible: dco_decode_bool(arr[10]),
+      properties: dco_decode_Map_String_String_None(arr[11]),
     );
   }
 
+  @protected
+  List<String> dco_decode_list_String(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_String).toList();
+  }
+
+  @protected
+  List<FlutterInterface> dco_decode_list_flutter_interface(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_flutter_interface).toList();
+  }
+
   @protected
   List<FlutterPeer> dco_decode_list_flutter_peer(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
@@ -607,6 +906,18 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     return (raw as List<dynamic>).map(dco_decode_record_string_string).toList();
   }
 
+  @protected
+  FlutterPeer? dco_decode_opt_box_autoadd_flutter_peer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_box_autoadd_flutter_peer(raw);
+  }
+
+  @protected
+  int? dco_decode_opt_box_autoadd_u_16(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_box_autoadd_u_16(raw);
+  }
+
   @protected
   (String, String) dco_decode_record_string_string(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
@@ -735,25 +1046,98 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     return utf8.decoder.convert(inner);
   }
 
+  @protected
+  FlutterPeer sse_decode_box_autoadd_flutter_peer(
+    SseDeserializer deserializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_flutter_peer(deserializer));
+  }
+
+  @protected
+  int sse_decode_box_autoadd_u_16(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_u_16(deserializer));
+  }
+
+  @protected
+  FlutterError sse_decode_flutter_error(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_code = sse_decode_String(deserializer);
+    var var_message = sse_decode_String(deserializer);
+    return FlutterError(code: var_code, message: var_message);
+  }
+
+  @protected
+  FlutterInterface sse_decode_flutter_interface(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_name = sse_decode_String(deserializer);
+    var var_ip = sse_decode_String(deserializer);
+    var var_isLoopback = sse_decode_bool(deserializer);
+    return FlutterInterface(
+      name: var_name,
+      ip: var_ip,
+      isLoopback: var_isLoopback,
+    );
+  }
+
   @protected
   FlutterPeer sse_decode_flutter_peer(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
     var var_id = sse_decode_String(deserializer);
     var var_name = sse_decode_String(deserializer);
+    var var_hostname = sse_decode_String(deserializer);
     var var_ip = sse_decode_String(deserializer);
+    var var_addresses = sse_decode_list_String(deserializer);
+    var var_addressPending = sse_decode_bool(deserializer);
     var var_port = sse_decode_u_16(deserializer);
+    var var_serviceType = sse_decode_String(deserializer);
     var var_deviceType = sse_decode_String(deserializer);
+    var var_freshness = sse_decode_String(deserializer);
+    var var_compatible = sse_decode_bool(deserializer);
     var var_properties = sse_decode_Map_String_String_None(deserializer);
     return FlutterPeer(
       id: var_id,
       name: var_name,
+      hostname: var_hostname,
       ip: var_ip,
+      addresses: var_addresses,
+      addressPending: var_addressPending,
       port: var_port,
+      serviceType: var_serviceType,
       deviceType: var_deviceType,
+      freshness: var_freshness,
+      compatible: var_compatible,
       properties: var_properties,
     );
   }
 
+  @protected
+  List<String> sse_decode_list_String(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <String>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_String(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  List<FlutterInterface> sse_decode_list_flutter_interface(
+    SseDeserializer deserializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <FlutterInterface>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_flutter_interface(deserializer));
+    }
+    return ans_;
+  }
+
   @protected
   List<FlutterPeer> sse_decode_list_flutter_peer(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -787,6 +1171,28 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     return ans_;
   }
 
+  @protected
+  FlutterPeer? sse_decode_opt_box_autoadd_flutter_peer(
+    SseDeserializer deserializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_box_autoadd_flutter_peer(deserializer));
+    } else {
+      return null;
+    }
+  }
+
+  @protected
+  int? sse_decode_opt_box_autoadd_u_16(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_box_autoadd_u_16(deserializer));
+    } else {
+      return null;
+    }
+  }
+
   @protected
   (String, String) sse_decode_record_string_string(
     SseDeserializer deserializer,
@@ -934,17 +1340,77 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     sse_encode_list_prim_u_8_strict(utf8.encoder.convert(self), serializer);
   }
 
+  @protected
+  void sse_encode_box_autoadd_flutter_peer(
+    FlutterPeer self,
+    SseSerializer serializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_flutter_peer(self, serializer);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_u_16(int self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_u_16(self, serializer);
+  }
+
+  @protected
+  void sse_encode_flutter_error(FlutterError self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.code, serializer);
+    sse_encode_String(self.message, serializer);
+  }
+
+  @protected
+  void sse_encode_flutter_interface(
+    FlutterInterface self,
+    SseSerializer serializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.name, serializer);
+    sse_encode_String(self.ip, serializer);
+    sse_encode_bool(self.isLoopback, serializer);
+  }
+
   @protected
   void sse_encode_flutter_peer(FlutterPeer self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
     sse_encode_String(self.id, serializer);
     sse_encode_String(self.name, serializer);
+    sse_encode_String(self.hostname, serializer);
     sse_encode_String(self.ip, serializer);
+    sse_encode_list_String(self.addresses, serializer);
+    sse_encode_bool(self.addressPending, serializer);
     sse_encode_u_16(self.port, serializer);
+    sse_encode_String(self.serviceType, serializer);
     sse_encode_String(self.deviceType, serializer);
+    sse_encode_String(self.freshness, serializer);
+    sse_encode_bool(self.compatible, serializer);
     sse_encode_Map_String_String_None(self.properties, serializer);
   }
 
+  @protected
+  void sse_encode_list_String(List<String> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_String(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_flutter_interface(
+    List<FlutterInterface> self,
+    SseSerializer serializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_flutter_interface(item, serializer);
+    }
+  }
+
   @protected
   void sse_encode_list_flutter_peer(
     List<FlutterPeer> self,
@@ -979,6 +1445,27 @@ class P2PBridgeApiImpl extends P2PBridgeApiImplPlatform
     }
   }
 
+  @protected
+  void sse_encode_opt_box_autoadd_flutter_peer(
+    FlutterPeer? self,
+    SseSerializer serializer,
+  ) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_box_autoadd_flutter_peer(self, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_16(int? self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_box_autoadd_u_16(self, serializer);
+    }
+  }
+
   @protected
   void sse_encode_record_string_string(
     (String, String) self,
@@ -1091,13 +1578,37 @@ class P2PEngineImpl extends RustOpaque implements P2PEngine {
   Future<String> getVersion() =>
       P2PBridge.instance.api.crateApiP2PEngineGetVersion(that: this);
 
+  Future<bool> isDiscoveryActive() =>
+      P2PBridge.instance.api.crateApiP2PEngineIsDiscoveryActive(that: this);
+
+  Future<List<FlutterInterface>> listInterfaces() =>
+      P2PBridge.instance.api.crateApiP2PEngineListInterfaces(that: this);
+
+  Future<FlutterPeer?> refreshPeer({required String id}) =>
+      P2PBridge.instance.api.crateApiP2PEngineRefreshPeer(that: this, id: id);
+
+  Future<List<FlutterPeer>> scanOnce({
+    required String deviceName,
+    required String deviceType,
+    required BigInt timeoutSeconds,
+  }) => P2PBridge.instance.api.crateApiP2PEngineScanOnce(
+    that: this,
+    deviceName: deviceName,
+    deviceType: deviceType,
+    timeoutSeconds: timeoutSeconds,
+  );
+
   Future<void> startDiscovery({
     required String deviceName,
     required String deviceType,
+    required int? port,
+    required Map<String, String> properties,
   }) => P2PBridge.instance.api.crateApiP2PEngineStartDiscovery(
     that: this,
     deviceName: deviceName,
     deviceType: deviceType,
+    port: port,
+    properties: properties,
   );
 
   Future<void> stopDiscovery() =>
@@ -70,9 +70,30 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
   @protected
   String dco_decode_String(dynamic raw);
 
+  @protected
+  bool dco_decode_bool(dynamic raw);
+
+  @protected
+  FlutterPeer dco_decode_box_autoadd_flutter_peer(dynamic raw);
+
+  @protected
+  int dco_decode_box_autoadd_u_16(dynamic raw);
+
+  @protected
+  FlutterError dco_decode_flutter_error(dynamic raw);
+
+  @protected
+  FlutterInterface dco_decode_flutter_interface(dynamic raw);
+
   @protected
   FlutterPeer dco_decode_flutter_peer(dynamic raw);
 
+  @protected
+  List<String> dco_decode_list_String(dynamic raw);
+
+  @protected
+  List<FlutterInterface> dco_decode_list_flutter_interface(dynamic raw);
+
   @protected
   List<FlutterPeer> dco_decode_list_flutter_peer(dynamic raw);
 
@@ -82,6 +103,12 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
   @protected
   List<(String, String)> dco_decode_list_record_string_string(dynamic raw);
 
+  @protected
+  FlutterPeer? dco_decode_opt_box_autoadd_flutter_peer(dynamic raw);
+
+  @protected
+  int? dco_decode_opt_box_autoadd_u_16(dynamic raw);
+
   @protected
   (String, String) dco_decode_record_string_string(dynamic raw);
 
@@ -144,9 +171,29 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
   @protected
   String sse_decode_String(SseDeserializer deserializer);
 
+  @protected
+  FlutterPeer sse_decode_box_autoadd_flutter_peer(SseDeserializer deserializer);
+
+  @protected
+  int sse_decode_box_autoadd_u_16(SseDeserializer deserializer);
+
+  @protected
+  FlutterError sse_decode_flutter_error(SseDeserializer deserializer);
+
+  @protected
+  FlutterInterface sse_decode_flutter_interface(SseDeserializer deserializer);
+
   @protected
   FlutterPeer sse_decode_flutter_peer(SseDeserializer deserializer);
 
+  @protected
+  List<String> sse_decode_list_String(SseDeserializer deserializer);
+
+  @protected
+  List<FlutterInterface> sse_decode_list_flutter_interface(
+    SseDeserializer deserializer,
+  );
+
   @protected
   List<FlutterPeer> sse_decode_list_flutter_peer(SseDeserializer deserializer);
 
@@ -158,6 +205,14 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
     SseDeserializer deserializer,
   );
 
+  @protected
+  FlutterPeer? sse_decode_opt_box_autoadd_flutter_peer(
+    SseDeserializer deserializer,
+  );
+
+  @protected
+  int? sse_decode_opt_box_autoadd_u_16(SseDeserializer deserializer);
+
   @protected
   (String, String) sse_decode_record_string_string(
     SseDeserializer deserializer,
@@ -235,9 +290,36 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
   @protected
   void sse_encode_String(String self, SseSerializer serializer);
 
+  @protected
+  void sse_encode_box_autoadd_flutter_peer(
+    FlutterPeer self,
+    SseSerializer serializer,
+  );
+
+  @protected
+  void sse_encode_box_autoadd_u_16(int self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_flutter_error(FlutterError self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_flutter_interface(
+    FlutterInterface self,
+    SseSerializer serializer,
+  );
+
   @protected
   void sse_encode_flutter_peer(FlutterPeer self, SseSerializer serializer);
 
+  @protected
+  void sse_encode_list_String(List<String> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_flutter_interface(
+    List<FlutterInterface> self,
+    SseSerializer serializer,
+  );
+
   @protected
   void sse_encode_list_flutter_peer(
     List<FlutterPeer> self,
@@ -256,6 +338,15 @@ abstract class P2PBridgeApiImplPlatform extends BaseApiImpl<P2PBridgeWire> {
     SseSerializer serializer,
   );
 
+  @protected
+  void sse_encode_opt_box_autoadd_flutter_peer(
+    FlutterPeer? self,
+    SseSerializer serializer,
+  );
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_16(int? self, SseSerializer serializer);
+
   @protected
   void sse_encode_record_string_string(
     (String, String) self,
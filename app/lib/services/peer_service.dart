@@ -78,6 +78,8 @@ class PeerService extends ChangeNotifier {
         await startPeerDiscovery(
           deviceName: deviceName,
           deviceType: deviceType,
+          port: null,
+          properties: const {},
         );
 
         print('P2P discovery started successfully');
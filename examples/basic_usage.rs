@@ -20,13 +20,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         properties,
         discovery_timeout: Duration::from_secs(10),
         announce_interval: Duration::from_secs(30),
+        ..DiscoveryConfig::default()
     };
     
     // Create peer discovery instance
     let discovery = PeerDiscovery::new(config)?;
     
-    // Subscribe to events
-    let mut event_receiver = discovery.subscribe();
+    // Subscribe to events without dropping any if we fall behind
+    let mut event_receiver = discovery.subscribe_unbounded();
     
     // Start the discovery service
     discovery.start().await?;
@@ -35,12 +36,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Spawn a task to handle events
     let _discovery_clone = discovery.clone();
     tokio::spawn(async move {
-        while let Ok(event) = event_receiver.recv().await {
+        while let Some(event) = event_receiver.recv().await {
             match event {
                 qopyapp::PeerEvent::PeerDiscovered(peer) => {
                     info!("New peer discovered: {} at {}:{}", 
                           peer.name, peer.ip, peer.port);
                 }
+                qopyapp::PeerEvent::PeerUpdated(peer) => {
+                    info!("Peer updated: {} at {}:{}", 
+                          peer.name, peer.ip, peer.port);
+                }
                 qopyapp::PeerEvent::PeerLost(peer) => {
                     info!("Peer lost: {}", peer.name);
                 }
@@ -50,6 +55,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 qopyapp::PeerEvent::ServiceStopped => {
                     info!("Service stopped");
                 }
+                qopyapp::PeerEvent::LocalAddressChanged { previous, current } => {
+                    info!("Local address changed: {} -> {}", previous, current);
+                }
+                qopyapp::PeerEvent::LocalPropertiesChanged(properties) => {
+                    info!("Local properties changed: {:?}", properties);
+                }
+                qopyapp::PeerEvent::ScanProgress { elapsed, peers_found } => {
+                    info!("Scanning for {:?}, {} peers so far", elapsed, peers_found);
+                }
                 qopyapp::PeerEvent::Error(err) => {
                     error!("Discovery error: {}", err);
                 }
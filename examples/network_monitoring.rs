@@ -32,11 +32,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         properties,
         discovery_timeout: Duration::from_secs(15),
         announce_interval: Duration::from_secs(30),
+        ..DiscoveryConfig::default()
     };
     
     // Create and start discovery
     let discovery = PeerDiscovery::new(config)?;
-    let mut event_receiver = discovery.subscribe();
+    let mut event_receiver = discovery.subscribe_unbounded();
     
     discovery.start().await?;
     info!("Network monitoring started");
@@ -46,7 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let monitor_task = tokio::spawn(async move {
         let mut last_peer_count: i32 = 0;
         
-        while let Ok(event) = event_receiver.recv().await {
+        while let Some(event) = event_receiver.recv().await {
             match event {
                 qopyapp::PeerEvent::PeerDiscovered(peer) => {
                     info!("🔍 New peer discovered: {} at {}:{}", 
@@ -59,6 +60,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     
                     last_peer_count += 1;
                 }
+                qopyapp::PeerEvent::PeerUpdated(peer) => {
+                    info!("🔄 Peer updated: {} at {}:{}", 
+                          peer.name, peer.ip, peer.port);
+                }
                 qopyapp::PeerEvent::PeerLost(peer) => {
                     warn!("❌ Peer lost: {}", peer.name);
                     last_peer_count = last_peer_count.saturating_sub(1);
@@ -69,6 +74,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 qopyapp::PeerEvent::ServiceStopped => {
                     info!("🛑 Discovery service stopped");
                 }
+                qopyapp::PeerEvent::LocalAddressChanged { previous, current } => {
+                    info!("🌐 Local address changed: {} -> {}", previous, current);
+                }
+                qopyapp::PeerEvent::LocalPropertiesChanged(properties) => {
+                    info!("🏷️ Local properties changed: {:?}", properties);
+                }
+                qopyapp::PeerEvent::ScanProgress { elapsed, peers_found } => {
+                    info!("📈 Scanning for {:?}, {} peers so far", elapsed, peers_found);
+                }
                 qopyapp::PeerEvent::Error(err) => {
                     error!("💥 Discovery error: {}", err);
                 }
@@ -1,49 +1,217 @@
 // Flutter Rust Bridge API module
-use crate::peer_discovery::{PeerDiscovery as CorePeerDiscovery, DiscoveryConfig, Peer as CorePeer};
+use crate::engine::{PeerCallback, PeerConnection};
+use crate::error::PeerDiscoveryError;
+use crate::peer_discovery::{get_network_interfaces, NetworkInterface, PeerDiscovery as CorePeerDiscovery, DiscoveryConfig, Peer as CorePeer, PeerSource};
 use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::error;
+use tracing_subscriber::util::SubscriberInitExt;
 
 // Flutter-compatible structures
 #[derive(Debug, Clone)]
 pub struct FlutterPeer {
     pub id: String,
     pub name: String,
+    pub hostname: String,
     pub ip: String,
+    pub addresses: Vec<String>,
+    /// No usable address yet, `ip` is "0.0.0.0" until one resolves
+    pub address_pending: bool,
     pub port: u16,
+    /// e.g. "_qopyapp._tcp.local.", needed to turn the peer back into a core `Peer`
+    pub service_type: String,
     pub device_type: String,
+    /// "fresh", "recent" or "stale", see `Freshness`
+    pub freshness: String,
+    /// False when the peer's protocol version is below the supported minimum
+    pub compatible: bool,
     pub properties: HashMap<String, String>,
 }
 
 impl From<CorePeer> for FlutterPeer {
     fn from(peer: CorePeer) -> Self {
+        let device_type = peer.device_type().to_string();
+        let freshness = peer.freshness().to_string();
+        
         FlutterPeer {
-            id: peer.name.clone(), // Using name as ID for now
+            id: peer.id,
             name: peer.name,
+            hostname: peer.hostname,
             ip: peer.ip.to_string(),
+            addresses: peer.addresses.iter().map(|addr| addr.to_string()).collect(),
+            address_pending: peer.address_pending,
             port: peer.port,
-            device_type: peer.properties.get("device_type")
-                .unwrap_or(&"unknown".to_string())
-                .clone(),
+            service_type: peer.service_type,
+            device_type,
+            freshness,
+            compatible: peer.compatible,
             properties: peer.properties,
         }
     }
 }
 
+// Back to the core type for Rust callers; `last_seen` restarts now since only the coarse freshness crossed the bridge
+impl TryFrom<FlutterPeer> for CorePeer {
+    type Error = FlutterError;
+    
+    fn try_from(peer: FlutterPeer) -> Result<Self, Self::Error> {
+        let parse = |addr: &str| addr.parse::<IpAddr>()
+            .map_err(|e| FlutterError::new("invalid_address", format!("{:?}: {}", addr, e)));
+        
+        let ip = parse(&peer.ip)?;
+        let addresses = peer.addresses.iter()
+            .map(|addr| parse(addr))
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(CorePeer {
+            id: peer.id,
+            name: peer.name,
+            hostname: peer.hostname,
+            ip,
+            addresses,
+            address_pending: peer.address_pending,
+            port: peer.port,
+            service_type: peer.service_type,
+            raw_properties: peer.properties.iter()
+                .map(|(key, value)| (key.clone(), value.as_bytes().to_vec()))
+                .collect(),
+            properties: peer.properties,
+            compatible: peer.compatible,
+            source: PeerSource::default(),
+            last_seen: SystemTime::now(),
+        })
+    }
+}
+
+// Local network interface as shown in the app's network settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlutterInterface {
+    pub name: String,
+    pub ip: String,
+    pub is_loopback: bool,
+}
+
+impl From<NetworkInterface> for FlutterInterface {
+    fn from(interface: NetworkInterface) -> Self {
+        FlutterInterface {
+            name: interface.name,
+            ip: interface.ip.to_string(),
+            is_loopback: interface.is_loopback,
+        }
+    }
+}
+
+// Error returned across the FFI boundary, `code` is stable for Dart to match on
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlutterError {
+    pub code: String,
+    pub message: String,
+}
+
+impl FlutterError {
+    pub(crate) fn new(code: &str, message: impl Into<String>) -> Self {
+        FlutterError {
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+    
+    pub(crate) fn not_started() -> Self {
+        FlutterError::new("not_started", "Discovery not started")
+    }
+}
+
+impl std::fmt::Display for FlutterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for FlutterError {}
+
+impl From<PeerDiscoveryError> for FlutterError {
+    fn from(err: PeerDiscoveryError) -> Self {
+        let code = match &err {
+            PeerDiscoveryError::MdnsError(_) => "mdns_error",
+            PeerDiscoveryError::DaemonInitFailed(_) => "daemon_init_failed",
+            PeerDiscoveryError::NetworkInterfaceError(_) => "network_interface_error",
+            PeerDiscoveryError::ServiceRegistrationFailed(_) => "service_registration_failed",
+            PeerDiscoveryError::ServiceDiscoveryFailed(_) => "service_discovery_failed",
+            PeerDiscoveryError::InvalidServiceType(_) => "invalid_service_type",
+            PeerDiscoveryError::InvalidDeviceType(_) => "invalid_device_type",
+            PeerDiscoveryError::DiscoveryTimeout(_) => "discovery_timeout",
+            PeerDiscoveryError::ConnectionFailed(_) => "connection_failed",
+            PeerDiscoveryError::SerializationError(_) => "serialization_error",
+            PeerDiscoveryError::IoError(_) => "io_error",
+        };
+        
+        FlutterError::new(code, err.to_string())
+    }
+}
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(5);
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Bound a start step so a hung mDNS daemon can't block the Dart caller forever
+async fn start_with_timeout<T>(
+    start_timeout: Duration,
+    start: impl Future<Output = Result<T, PeerDiscoveryError>>,
+) -> Result<T, PeerDiscoveryError> {
+    tokio::time::timeout(start_timeout, start)
+        .await
+        .unwrap_or_else(|_| Err(PeerDiscoveryError::DiscoveryTimeout(format!(
+            "discovery did not start within {:?}",
+            start_timeout
+        ))))
+}
+
+// Caller properties are advertised as-is, except the reserved keys which we always set
+fn discovery_config(device_name: String, device_type: String, port: Option<u16>, mut properties: HashMap<String, String>) -> DiscoveryConfig {
+    properties.insert("version".to_string(), "1.0.0".to_string());
+    properties.insert("device_type".to_string(), device_type);
+    
+    DiscoveryConfig {
+        service_type: "_qopyapp._tcp.local.".to_string(),
+        service_name: device_name,
+        port: port.unwrap_or(DEFAULT_PORT),
+        properties,
+        discovery_timeout: Duration::from_secs(10),
+        announce_interval: Duration::from_secs(30),
+        ..DiscoveryConfig::default()
+    }
+}
+
 pub struct P2PEngine {
-    discovery: Option<Arc<Mutex<CorePeerDiscovery>>>,
+    pub(crate) discovery: Option<Arc<Mutex<CorePeerDiscovery>>>,
+    pub(crate) peer_callback: Option<PeerCallback>,
+    pub(crate) callback_task: Option<JoinHandle<()>>,
+    pub(crate) start_timeout: Duration,
+    pub(crate) connections: Arc<Mutex<HashMap<String, PeerConnection>>>,
+    pub(crate) eviction_task: Option<JoinHandle<()>>,
+}
+
+impl Default for P2PEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl P2PEngine {
+    /// Create an engine, logging is left to the host app or `init_logging`
     pub fn new() -> Self {
-        // Initialize logging
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter("info")
-            .try_init();
-        
         Self {
             discovery: None,
+            peer_callback: None,
+            callback_task: None,
+            start_timeout: DEFAULT_START_TIMEOUT,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            eviction_task: None,
         }
     }
     
@@ -51,42 +219,56 @@ impl P2PEngine {
         "1.0.0".to_string()
     }
     
-    pub async fn start_discovery(&mut self, device_name: String, device_type: String) -> Result<(), String> {
-        let mut properties = HashMap::new();
-        properties.insert("version".to_string(), "1.0.0".to_string());
-        properties.insert("device_type".to_string(), device_type);
-        
-        let config = DiscoveryConfig {
-            service_type: "_qopyapp._tcp.local.".to_string(),
-            service_name: device_name,
-            port: 8080,
-            properties,
-            discovery_timeout: Duration::from_secs(10),
-            announce_interval: Duration::from_secs(30),
-        };
-        
-        let discovery = CorePeerDiscovery::new(config)
-            .map_err(|e| e.to_string())?;
+    /// Start advertising and browsing, `port` defaults to 8080 when not provided
+    pub async fn start_discovery(
+        &mut self,
+        device_name: String,
+        device_type: String,
+        port: Option<u16>,
+        properties: HashMap<String, String>,
+    ) -> Result<(), FlutterError> {
+        let config = discovery_config(device_name, device_type, port, properties);
         
-        discovery.start().await
-            .map_err(|e| e.to_string())?;
+        let discovery = CorePeerDiscovery::new(config)?;
+        if let Err(e) = start_with_timeout(self.start_timeout, discovery.start()).await {
+            // Tear down whatever part of the service came up
+            let _ = discovery.stop().await;
+            return Err(e.into());
+        }
         
         self.discovery = Some(Arc::new(Mutex::new(discovery)));
+        self.restart_callback_task().await;
+        self.restart_eviction_task().await;
         
         Ok(())
     }
     
-    pub async fn stop_discovery(&mut self) -> Result<(), String> {
+    pub async fn stop_discovery(&mut self) -> Result<(), FlutterError> {
+        if let Some(task) = self.callback_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.eviction_task.take() {
+            task.abort();
+        }
+        self.connections.lock().await.clear();
+        
         if let Some(discovery) = &self.discovery {
             let discovery = discovery.lock().await;
-            discovery.stop().await
-                .map_err(|e| e.to_string())?;
+            discovery.stop().await?;
         }
 
         self.discovery = None;
         Ok(())
     }
     
+    pub async fn is_discovery_active(&self) -> bool {
+        if let Some(discovery) = &self.discovery {
+            discovery.lock().await.is_running().await
+        } else {
+            false
+        }
+    }
+    
     pub async fn get_peers(&self) -> Vec<FlutterPeer> {
         if let Some(discovery) = &self.discovery {
             let discovery = discovery.lock().await;
@@ -99,20 +281,57 @@ impl P2PEngine {
         }
     }
     
-    pub async fn discover_peers_with_timeout(&self, timeout_seconds: u64) -> Result<Vec<FlutterPeer>, String> {
+    /// Every local interface address, loopback included so the UI can decide what to show
+    ///
+    /// Empty when the interfaces can't be enumerated, the failure is logged.
+    pub async fn list_interfaces(&self) -> Vec<FlutterInterface> {
+        match get_network_interfaces().await {
+            Ok(interfaces) => interfaces.into_iter().map(FlutterInterface::from).collect(),
+            Err(e) => {
+                error!("Failed to list network interfaces: {}", e);
+                Vec::new()
+            }
+        }
+    }
+    
+    /// Ask the network for fresh details of one peer, `None` when it isn't known
+    pub async fn refresh_peer(&self, id: String) -> Option<FlutterPeer> {
+        let discovery = self.discovery.as_ref()?.lock().await;
+        discovery.refresh_peer(&id, REFRESH_TIMEOUT).await.map(FlutterPeer::from)
+    }
+    
+    pub async fn discover_peers_with_timeout(&self, timeout_seconds: u64) -> Result<Vec<FlutterPeer>, FlutterError> {
         if let Some(discovery) = &self.discovery {
             let discovery = discovery.lock().await;
             let peers = discovery.discover_peers(Some(Duration::from_secs(timeout_seconds)))
-                .await
-                .map_err(|e| e.to_string())?;
+                .await?;
 
             Ok(peers.into_iter()
                 .map(FlutterPeer::from)
                 .collect())
         } else {
-            Err("Discovery not started".to_string())
+            Err(FlutterError::not_started())
         }
     }
+    
+    /// Start discovery, collect peers for `timeout_seconds` and stop again, e.g. for pull-to-refresh
+    ///
+    /// An already running discovery is reused and left running.
+    pub async fn scan_once(&mut self, device_name: String, device_type: String, timeout_seconds: u64) -> Result<Vec<FlutterPeer>, FlutterError> {
+        if self.is_discovery_active().await {
+            return self.discover_peers_with_timeout(timeout_seconds).await;
+        }
+        
+        self.start_discovery(device_name, device_type, None, HashMap::new()).await?;
+        let peers = self.discover_peers_with_timeout(timeout_seconds).await;
+        
+        // Always stop, but report a scan failure over a stop failure
+        let stopped = self.stop_discovery().await;
+        let peers = peers?;
+        stopped?;
+        
+        Ok(peers)
+    }
 }
 
 // Static instance for simplified FFI
@@ -125,19 +344,42 @@ pub fn get_engine() -> Arc<Mutex<P2PEngine>> {
 }
 
 // Simple FFI functions for Flutter
+
+/// Install a global log subscriber at `level` (e.g. "info", "p2p_core=debug"), call at most once
+pub fn init_logging(level: String) -> Result<(), FlutterError> {
+    log_subscriber(&level)?
+        .try_init()
+        .map_err(|e| FlutterError::new("logging_already_initialized", e.to_string()))
+}
+
+/// The subscriber `init_logging` installs
+fn log_subscriber(level: &str) -> Result<impl tracing::Subscriber + Send + Sync, FlutterError> {
+    let filter = tracing_subscriber::EnvFilter::try_new(level)
+        .map_err(|e| FlutterError::new("invalid_log_level", e.to_string()))?;
+    
+    Ok(tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .finish())
+}
+
 pub async fn init_p2p_engine() -> Result<String, String> {
     let engine = get_engine();
     let engine = engine.lock().await;
     Ok(engine.get_version())
 }
 
-pub async fn start_peer_discovery(device_name: String, device_type: String) -> Result<(), String> {
+pub async fn start_peer_discovery(
+    device_name: String,
+    device_type: String,
+    port: Option<u16>,
+    properties: HashMap<String, String>,
+) -> Result<(), FlutterError> {
     let engine = get_engine();
     let mut engine = engine.lock().await;
-    engine.start_discovery(device_name, device_type).await
+    engine.start_discovery(device_name, device_type, port, properties).await
 }
 
-pub async fn stop_peer_discovery() -> Result<(), String> {
+pub async fn stop_peer_discovery() -> Result<(), FlutterError> {
     let engine = get_engine();
     let mut engine = engine.lock().await;
     engine.stop_discovery().await
@@ -148,3 +390,206 @@ pub async fn get_discovered_peers() -> Result<Vec<FlutterPeer>, String> {
     let engine = engine.lock().await;
     Ok(engine.get_peers().await)
 }
+
+pub async fn list_network_interfaces() -> Vec<FlutterInterface> {
+    let engine = get_engine();
+    let engine = engine.lock().await;
+    engine.list_interfaces().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_flutter_error_codes_from_discovery_errors() {
+        let timeout: FlutterError = PeerDiscoveryError::DiscoveryTimeout("5s".to_string()).into();
+        assert_eq!(timeout.code, "discovery_timeout");
+        assert_eq!(timeout.message, "Timeout waiting for discovery: 5s");
+        
+        let interface: FlutterError = PeerDiscoveryError::NetworkInterfaceError("no interfaces".to_string()).into();
+        assert_eq!(interface.code, "network_interface_error");
+        assert_ne!(interface.code, timeout.code);
+        
+        let invalid: FlutterError = PeerDiscoveryError::InvalidServiceType("bad".to_string()).into();
+        assert_eq!(invalid.code, "invalid_service_type");
+    }
+    
+    #[test]
+    fn test_discovery_config_port() {
+        let config = discovery_config("device".to_string(), "desktop".to_string(), None, HashMap::new());
+        assert_eq!(config.port, 8080);
+        
+        let config = discovery_config("device".to_string(), "desktop".to_string(), Some(9123), HashMap::new());
+        assert_eq!(config.port, 9123);
+    }
+    
+    #[test]
+    fn test_discovery_config_properties() {
+        let mut properties = HashMap::new();
+        properties.insert("capabilities".to_string(), "clipboard".to_string());
+        properties.insert("device_type".to_string(), "toaster".to_string());
+        
+        let config = discovery_config("device".to_string(), "phone".to_string(), None, properties);
+        assert_eq!(config.properties.get("capabilities").map(String::as_str), Some("clipboard"));
+        assert_eq!(config.properties.get("device_type").map(String::as_str), Some("phone"));
+        assert_eq!(config.properties.get("version").map(String::as_str), Some("1.0.0"));
+    }
+    
+    #[tokio::test]
+    async fn test_start_discovery_registers_custom_port() {
+        let mut engine = P2PEngine::new();
+        engine.start_discovery("port-test".to_string(), "desktop".to_string(), Some(9124), HashMap::new()).await.unwrap();
+        
+        let discovery = engine.discovery.clone().unwrap();
+        assert_eq!(discovery.lock().await.port().await, 9124);
+        
+        engine.stop_discovery().await.unwrap();
+    }
+    
+    #[test]
+    fn test_engine_construction_does_not_init_logging() {
+        let _first = P2PEngine::new();
+        let _second = P2PEngine::new();
+        
+        // No test installs a global subscriber, so one set here came from the engines
+        assert!(!tracing::dispatcher::has_been_set());
+        
+        assert_eq!(init_logging("p2p_core=loud".to_string()).unwrap_err().code, "invalid_log_level");
+        // Check what init_logging would install without making it global
+        tracing::subscriber::with_default(log_subscriber("info").unwrap(), || {
+            assert!(tracing::enabled!(tracing::Level::INFO));
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+        });
+    }
+    
+    #[tokio::test]
+    async fn test_slow_start_times_out() {
+        let slow_start = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        };
+        
+        let err = start_with_timeout(Duration::from_millis(50), slow_start).await.unwrap_err();
+        assert!(matches!(err, PeerDiscoveryError::DiscoveryTimeout(_)));
+        assert_eq!(FlutterError::from(err).code, "discovery_timeout");
+    }
+    
+    #[tokio::test]
+    async fn test_scan_once_stops_afterwards() {
+        let mut engine = P2PEngine::new();
+        
+        engine.scan_once("scan-test".to_string(), "phone".to_string(), 1).await.unwrap();
+        assert!(!engine.is_discovery_active().await);
+        
+        // A rejected timeout still tears the discovery down
+        let err = engine.scan_once("scan-test".to_string(), "phone".to_string(), 0).await.unwrap_err();
+        assert_eq!(err.code, "discovery_timeout");
+        assert!(!engine.is_discovery_active().await);
+        assert!(engine.discovery.is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_discover_without_start_is_not_started() {
+        let engine = P2PEngine::new();
+        let err = engine.discover_peers_with_timeout(1).await.unwrap_err();
+        assert_eq!(err.code, "not_started");
+    }
+    
+    fn resolved_peer(port: u16) -> mdns_sd::ServiceEvent {
+        let info = mdns_sd::ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            "refresh-peer",
+            "refresh-peer.local.",
+            "192.168.1.30",
+            port,
+            &[(crate::INSTANCE_ID_PROPERTY, "refresh-peer"), ("device_type", "phone")][..],
+        ).unwrap();
+        mdns_sd::ServiceEvent::ServiceResolved(info)
+    }
+    
+    #[test]
+    fn test_flutter_interface_from_network_interface() {
+        let interface = FlutterInterface::from(NetworkInterface {
+            name: "wlan0".to_string(),
+            ip: "192.168.1.10".parse().unwrap(),
+            is_loopback: false,
+            is_up: true,
+            supports_multicast: true,
+        });
+        assert_eq!(interface, FlutterInterface {
+            name: "wlan0".to_string(),
+            ip: "192.168.1.10".to_string(),
+            is_loopback: false,
+        });
+    }
+    
+    #[tokio::test]
+    async fn test_list_interfaces() {
+        let engine = P2PEngine::new();
+        let listed = engine.list_interfaces().await;
+        let interfaces = get_network_interfaces().await.unwrap();
+        
+        // Nothing is filtered out, loopback and down interfaces included
+        assert_eq!(listed.len(), interfaces.len());
+        for (listed, interface) in listed.iter().zip(interfaces) {
+            assert_eq!(listed.name, interface.name);
+            assert_eq!(listed.ip, interface.ip.to_string());
+            assert_eq!(listed.is_loopback, interface.is_loopback);
+        }
+        assert!(listed.iter().any(|interface| interface.is_loopback));
+    }
+    
+    #[tokio::test]
+    async fn test_flutter_peer_round_trip() {
+        let discovery = CorePeerDiscovery::new(DiscoveryConfig {
+            test_mode: true,
+            bind_ip: Some(IpAddr::from([127, 0, 0, 1])),
+            ..discovery_config("round-trip".to_string(), "desktop".to_string(), None, HashMap::new())
+        }).unwrap();
+        discovery.start().await.unwrap();
+        discovery.inject_service_event(resolved_peer(9000)).unwrap();
+        let peer = discovery.wait_for_peer(|peer| peer.id == "refresh-peer", Duration::from_secs(1)).await.unwrap();
+        discovery.stop().await.unwrap();
+        
+        let flutter_peer = FlutterPeer::from(peer.clone());
+        let round_tripped = CorePeer::try_from(flutter_peer.clone()).unwrap();
+        assert!(round_tripped.content_eq(&peer));
+        
+        // Instance names may contain "._", the service type travels on its own
+        let dotted = CorePeer { name: "my._peer._qopyapp._tcp.local.".to_string(), ..peer.clone() };
+        assert_eq!(CorePeer::try_from(FlutterPeer::from(dotted)).unwrap().service_type, peer.service_type);
+        
+        let malformed = FlutterPeer { ip: "not-an-ip".to_string(), ..flutter_peer };
+        assert_eq!(CorePeer::try_from(malformed).unwrap_err().code, "invalid_address");
+    }
+    
+    #[tokio::test]
+    async fn test_refresh_peer() {
+        let discovery = CorePeerDiscovery::new(DiscoveryConfig {
+            test_mode: true,
+            bind_ip: Some(IpAddr::from([127, 0, 0, 1])),
+            ..discovery_config("refresh".to_string(), "desktop".to_string(), None, HashMap::new())
+        }).unwrap();
+        discovery.start().await.unwrap();
+        discovery.inject_service_event(resolved_peer(9000)).unwrap();
+        discovery.wait_for_peer(|peer| peer.id == "refresh-peer", Duration::from_secs(1)).await.unwrap();
+        
+        let mut engine = P2PEngine::new();
+        assert!(engine.refresh_peer("refresh-peer".to_string()).await.is_none());
+        engine.discovery = Some(Arc::new(Mutex::new(discovery.clone())));
+        
+        // The peer answers the new query on another port
+        let injector = discovery.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            injector.inject_service_event(resolved_peer(9001)).unwrap();
+        });
+        
+        let refreshed = engine.refresh_peer("refresh-peer".to_string()).await.unwrap();
+        assert_eq!(refreshed.port, 9001);
+        assert!(engine.refresh_peer("unknown".to_string()).await.is_none());
+        
+        engine.stop_discovery().await.unwrap();
+    }
+}
@@ -39,7 +39,7 @@ flutter_rust_bridge::frb_generated_boilerplate!(
     default_rust_auto_opaque = RustAutoOpaqueMoi,
 );
 pub(crate) const FLUTTER_RUST_BRIDGE_CODEGEN_VERSION: &str = "2.11.1";
-pub(crate) const FLUTTER_RUST_BRIDGE_CODEGEN_CONTENT_HASH: i32 = 475141219;
+pub(crate) const FLUTTER_RUST_BRIDGE_CODEGEN_CONTENT_HASH: i32 = -1804626358;
 
 // Section: executor
 
@@ -75,7 +75,7 @@ fn wire__crate__api__P2PEngine_discover_peers_with_timeout_impl(
             let api_timeout_seconds = <u64>::sse_decode(&mut deserializer);
             deserializer.end();
             move |context| async move {
-                transform_result_sse::<_, String>(
+                transform_result_sse::<_, crate::api::FlutterError>(
                     (move || async move {
                         let mut api_that_guard = None;
                         let decode_indices_ =
@@ -214,6 +214,120 @@ fn wire__crate__api__P2PEngine_get_version_impl(
         },
     )
 }
+fn wire__crate__api__P2PEngine_is_discovery_active_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_async::<flutter_rust_bridge::for_generated::SseCodec, _, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "P2PEngine_is_discovery_active",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            let api_that = <RustOpaqueMoi<
+                flutter_rust_bridge::for_generated::RustAutoOpaqueInner<P2PEngine>,
+            >>::sse_decode(&mut deserializer);
+            deserializer.end();
+            move |context| async move {
+                transform_result_sse::<_, ()>(
+                    (move || async move {
+                        let mut api_that_guard = None;
+                        let decode_indices_ =
+                            flutter_rust_bridge::for_generated::lockable_compute_decode_order(
+                                vec![flutter_rust_bridge::for_generated::LockableOrderInfo::new(
+                                    &api_that, 0, false,
+                                )],
+                            );
+                        for i in decode_indices_ {
+                            match i {
+                                0 => {
+                                    api_that_guard =
+                                        Some(api_that.lockable_decode_async_ref().await)
+                                }
+                                _ => unreachable!(),
+                            }
+                        }
+                        let api_that_guard = api_that_guard.unwrap();
+                        let output_ok = Result::<_, ()>::Ok(
+                            crate::api::P2PEngine::is_discovery_active(&*api_that_guard).await,
+                        )?;
+                        Ok(output_ok)
+                    })()
+                    .await,
+                )
+            }
+        },
+    )
+}
+fn wire__crate__api__P2PEngine_list_interfaces_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_async::<flutter_rust_bridge::for_generated::SseCodec, _, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "P2PEngine_list_interfaces",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            let api_that = <RustOpaqueMoi<
+                flutter_rust_bridge::for_generated::RustAutoOpaqueInner<P2PEngine>,
+            >>::sse_decode(&mut deserializer);
+            deserializer.end();
+            move |context| async move {
+                transform_result_sse::<_, ()>(
+                    (move || async move {
+                        let mut api_that_guard = None;
+                        let decode_indices_ =
+                            flutter_rust_bridge::for_generated::lockable_compute_decode_order(
+                                vec![flutter_rust_bridge::for_generated::LockableOrderInfo::new(
+                                    &api_that, 0, false,
+                                )],
+                            );
+                        for i in decode_indices_ {
+                            match i {
+                                0 => {
+                                    api_that_guard =
+                                        Some(api_that.lockable_decode_async_ref().await)
+                                }
+                                _ => unreachable!(),
+                            }
+                        }
+                        let api_that_guard = api_that_guard.unwrap();
+                        let output_ok = Result::<_, ()>::Ok(
+                            crate::api::P2PEngine::list_interfaces(&*api_that_guard).await,
+                        )?;
+                        Ok(output_ok)
+                    })()
+                    .await,
+                )
+            }
+        },
+    )
+}
 fn wire__crate__api__P2PEngine_new_impl(
     port_: flutter_rust_bridge::for_generated::MessagePort,
     ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
@@ -246,6 +360,128 @@ fn wire__crate__api__P2PEngine_new_impl(
         },
     )
 }
+fn wire__crate__api__P2PEngine_refresh_peer_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_async::<flutter_rust_bridge::for_generated::SseCodec, _, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "P2PEngine_refresh_peer",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            let api_that = <RustOpaqueMoi<
+                flutter_rust_bridge::for_generated::RustAutoOpaqueInner<P2PEngine>,
+            >>::sse_decode(&mut deserializer);
+            let api_id = <String>::sse_decode(&mut deserializer);
+            deserializer.end();
+            move |context| async move {
+                transform_result_sse::<_, ()>(
+                    (move || async move {
+                        let mut api_that_guard = None;
+                        let decode_indices_ =
+                            flutter_rust_bridge::for_generated::lockable_compute_decode_order(
+                                vec![flutter_rust_bridge::for_generated::LockableOrderInfo::new(
+                                    &api_that, 0, false,
+                                )],
+                            );
+                        for i in decode_indices_ {
+                            match i {
+                                0 => {
+                                    api_that_guard =
+                                        Some(api_that.lockable_decode_async_ref().await)
+                                }
+                                _ => unreachable!(),
+                            }
+                        }
+                        let api_that_guard = api_that_guard.unwrap();
+                        let output_ok = Result::<_, ()>::Ok(
+                            crate::api::P2PEngine::refresh_peer(&*api_that_guard, api_id).await,
+                        )?;
+                        Ok(output_ok)
+                    })()
+                    .await,
+                )
+            }
+        },
+    )
+}
+fn wire__crate__api__P2PEngine_scan_once_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_async::<flutter_rust_bridge::for_generated::SseCodec, _, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "P2PEngine_scan_once",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            let api_that = <RustOpaqueMoi<
+                flutter_rust_bridge::for_generated::RustAutoOpaqueInner<P2PEngine>,
+            >>::sse_decode(&mut deserializer);
+            let api_device_name = <String>::sse_decode(&mut deserializer);
+            let api_device_type = <String>::sse_decode(&mut deserializer);
+            let api_timeout_seconds = <u64>::sse_decode(&mut deserializer);
+            deserializer.end();
+            move |context| async move {
+                transform_result_sse::<_, crate::api::FlutterError>(
+                    (move || async move {
+                        let mut api_that_guard = None;
+                        let decode_indices_ =
+                            flutter_rust_bridge::for_generated::lockable_compute_decode_order(
+                                vec![flutter_rust_bridge::for_generated::LockableOrderInfo::new(
+                                    &api_that, 0, true,
+                                )],
+                            );
+                        for i in decode_indices_ {
+                            match i {
+                                0 => {
+                                    api_that_guard =
+                                        Some(api_that.lockable_decode_async_ref_mut().await)
+                                }
+                                _ => unreachable!(),
+                            }
+                        }
+                        let mut api_that_guard = api_that_guard.unwrap();
+                        let output_ok = crate::api::P2PEngine::scan_once(
+                            &mut *api_that_guard,
+                            api_device_name,
+                            api_device_type,
+                            api_timeout_seconds,
+                        )
+                        .await?;
+                        Ok(output_ok)
+                    })()
+                    .await,
+                )
+            }
+        },
+    )
+}
 fn wire__crate__api__P2PEngine_start_discovery_impl(
     port_: flutter_rust_bridge::for_generated::MessagePort,
     ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
@@ -273,9 +509,12 @@ fn wire__crate__api__P2PEngine_start_discovery_impl(
             >>::sse_decode(&mut deserializer);
             let api_device_name = <String>::sse_decode(&mut deserializer);
             let api_device_type = <String>::sse_decode(&mut deserializer);
+            let api_port = <Option<u16>>::sse_decode(&mut deserializer);
+            let api_properties =
+                <std::collections::HashMap<String, String>>::sse_decode(&mut deserializer);
             deserializer.end();
             move |context| async move {
-                transform_result_sse::<_, String>(
+                transform_result_sse::<_, crate::api::FlutterError>(
                     (move || async move {
                         let mut api_that_guard = None;
                         let decode_indices_ =
@@ -298,6 +537,8 @@ fn wire__crate__api__P2PEngine_start_discovery_impl(
                             &mut *api_that_guard,
                             api_device_name,
                             api_device_type,
+                            api_port,
+                            api_properties,
                         )
                         .await?;
                         Ok(output_ok)
@@ -335,7 +576,7 @@ fn wire__crate__api__P2PEngine_stop_discovery_impl(
             >>::sse_decode(&mut deserializer);
             deserializer.end();
             move |context| async move {
-                transform_result_sse::<_, String>(
+                transform_result_sse::<_, crate::api::FlutterError>(
                     (move || async move {
                         let mut api_that_guard = None;
                         let decode_indices_ =
@@ -431,6 +672,39 @@ fn wire__crate__api__get_engine_impl(
         },
     )
 }
+fn wire__crate__api__init_logging_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_normal::<flutter_rust_bridge::for_generated::SseCodec, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "init_logging",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            let api_level = <String>::sse_decode(&mut deserializer);
+            deserializer.end();
+            move |context| {
+                transform_result_sse::<_, crate::api::FlutterError>((move || {
+                    let output_ok = crate::api::init_logging(api_level)?;
+                    Ok(output_ok)
+                })())
+            }
+        },
+    )
+}
 fn wire__crate__api__init_p2p_engine_impl(
     port_: flutter_rust_bridge::for_generated::MessagePort,
     ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
@@ -466,6 +740,42 @@ fn wire__crate__api__init_p2p_engine_impl(
         },
     )
 }
+fn wire__crate__api__list_network_interfaces_impl(
+    port_: flutter_rust_bridge::for_generated::MessagePort,
+    ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
+    rust_vec_len_: i32,
+    data_len_: i32,
+) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap_async::<flutter_rust_bridge::for_generated::SseCodec, _, _, _>(
+        flutter_rust_bridge::for_generated::TaskInfo {
+            debug_name: "list_network_interfaces",
+            port: Some(port_),
+            mode: flutter_rust_bridge::for_generated::FfiCallMode::Normal,
+        },
+        move || {
+            let message = unsafe {
+                flutter_rust_bridge::for_generated::Dart2RustMessageSse::from_wire(
+                    ptr_,
+                    rust_vec_len_,
+                    data_len_,
+                )
+            };
+            let mut deserializer =
+                flutter_rust_bridge::for_generated::SseDeserializer::new(message);
+            deserializer.end();
+            move |context| async move {
+                transform_result_sse::<_, ()>(
+                    (move || async move {
+                        let output_ok =
+                            Result::<_, ()>::Ok(crate::api::list_network_interfaces().await)?;
+                        Ok(output_ok)
+                    })()
+                    .await,
+                )
+            }
+        },
+    )
+}
 fn wire__crate__api__start_peer_discovery_impl(
     port_: flutter_rust_bridge::for_generated::MessagePort,
     ptr_: flutter_rust_bridge::for_generated::PlatformGeneralizedUint8ListPtr,
@@ -490,13 +800,20 @@ fn wire__crate__api__start_peer_discovery_impl(
                 flutter_rust_bridge::for_generated::SseDeserializer::new(message);
             let api_device_name = <String>::sse_decode(&mut deserializer);
             let api_device_type = <String>::sse_decode(&mut deserializer);
+            let api_port = <Option<u16>>::sse_decode(&mut deserializer);
+            let api_properties =
+                <std::collections::HashMap<String, String>>::sse_decode(&mut deserializer);
             deserializer.end();
             move |context| async move {
-                transform_result_sse::<_, String>(
+                transform_result_sse::<_, crate::api::FlutterError>(
                     (move || async move {
-                        let output_ok =
-                            crate::api::start_peer_discovery(api_device_name, api_device_type)
-                                .await?;
+                        let output_ok = crate::api::start_peer_discovery(
+                            api_device_name,
+                            api_device_type,
+                            api_port,
+                            api_properties,
+                        )
+                        .await?;
                         Ok(output_ok)
                     })()
                     .await,
@@ -529,7 +846,7 @@ fn wire__crate__api__stop_peer_discovery_impl(
                 flutter_rust_bridge::for_generated::SseDeserializer::new(message);
             deserializer.end();
             move |context| async move {
-                transform_result_sse::<_, String>(
+                transform_result_sse::<_, crate::api::FlutterError>(
                     (move || async move {
                         let output_ok = crate::api::stop_peer_discovery().await?;
                         Ok(output_ok)
@@ -610,27 +927,111 @@ impl SseDecode for String {
     }
 }
 
+impl SseDecode for Option<crate::api::FlutterPeer> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        if (<bool>::sse_decode(deserializer)) {
+            return Some(<crate::api::FlutterPeer>::sse_decode(deserializer));
+        } else {
+            return None;
+        }
+    }
+}
+
+impl SseDecode for Option<u16> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        if (<bool>::sse_decode(deserializer)) {
+            return Some(<u16>::sse_decode(deserializer));
+        } else {
+            return None;
+        }
+    }
+}
+
+impl SseDecode for crate::api::FlutterError {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        let mut var_code = <String>::sse_decode(deserializer);
+        let mut var_message = <String>::sse_decode(deserializer);
+        return crate::api::FlutterError {
+            code: var_code,
+            message: var_message,
+        };
+    }
+}
+
+impl SseDecode for crate::api::FlutterInterface {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        let mut var_name = <String>::sse_decode(deserializer);
+        let mut var_ip = <String>::sse_decode(deserializer);
+        let mut var_isLoopback = <bool>::sse_decode(deserializer);
+        return crate::api::FlutterInterface {
+            name: var_name,
+            ip: var_ip,
+            is_loopback: var_isLoopback,
+        };
+    }
+}
+
 impl SseDecode for crate::api::FlutterPeer {
     // Codec=Sse (Serialization based), see doc to use other codecs
     fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
         let mut var_id = <String>::sse_decode(deserializer);
         let mut var_name = <String>::sse_decode(deserializer);
+        let mut var_hostname = <String>::sse_decode(deserializer);
         let mut var_ip = <String>::sse_decode(deserializer);
+        let mut var_addresses = <Vec<String>>::sse_decode(deserializer);
+        let mut var_addressPending = <bool>::sse_decode(deserializer);
         let mut var_port = <u16>::sse_decode(deserializer);
+        let mut var_serviceType = <String>::sse_decode(deserializer);
         let mut var_deviceType = <String>::sse_decode(deserializer);
+        let mut var_freshness = <String>::sse_decode(deserializer);
+        let mut var_compatible = <bool>::sse_decode(deserializer);
         let mut var_properties =
             <std::collections::HashMap<String, String>>::sse_decode(deserializer);
         return crate::api::FlutterPeer {
             id: var_id,
             name: var_name,
+            hostname: var_hostname,
             ip: var_ip,
+            addresses: var_addresses,
+            address_pending: var_addressPending,
             port: var_port,
+            service_type: var_serviceType,
             device_type: var_deviceType,
+            freshness: var_freshness,
+            compatible: var_compatible,
             properties: var_properties,
         };
     }
 }
 
+impl SseDecode for Vec<String> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        let mut len_ = <i32>::sse_decode(deserializer);
+        let mut ans_ = vec![];
+        for idx_ in 0..len_ {
+            ans_.push(<String>::sse_decode(deserializer));
+        }
+        return ans_;
+    }
+}
+
+impl SseDecode for Vec<crate::api::FlutterInterface> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
+        let mut len_ = <i32>::sse_decode(deserializer);
+        let mut ans_ = vec![];
+        for idx_ in 0..len_ {
+            ans_.push(<crate::api::FlutterInterface>::sse_decode(deserializer));
+        }
+        return ans_;
+    }
+}
+
 impl SseDecode for Vec<crate::api::FlutterPeer> {
     // Codec=Sse (Serialization based), see doc to use other codecs
     fn sse_decode(deserializer: &mut flutter_rust_bridge::for_generated::SseDeserializer) -> Self {
@@ -740,14 +1141,22 @@ fn pde_ffi_dispatcher_primary_impl(
         ),
         2 => wire__crate__api__P2PEngine_get_peers_impl(port, ptr, rust_vec_len, data_len),
         3 => wire__crate__api__P2PEngine_get_version_impl(port, ptr, rust_vec_len, data_len),
-        4 => wire__crate__api__P2PEngine_new_impl(port, ptr, rust_vec_len, data_len),
-        5 => wire__crate__api__P2PEngine_start_discovery_impl(port, ptr, rust_vec_len, data_len),
-        6 => wire__crate__api__P2PEngine_stop_discovery_impl(port, ptr, rust_vec_len, data_len),
-        7 => wire__crate__api__get_discovered_peers_impl(port, ptr, rust_vec_len, data_len),
-        8 => wire__crate__api__get_engine_impl(port, ptr, rust_vec_len, data_len),
-        9 => wire__crate__api__init_p2p_engine_impl(port, ptr, rust_vec_len, data_len),
-        10 => wire__crate__api__start_peer_discovery_impl(port, ptr, rust_vec_len, data_len),
-        11 => wire__crate__api__stop_peer_discovery_impl(port, ptr, rust_vec_len, data_len),
+        4 => {
+            wire__crate__api__P2PEngine_is_discovery_active_impl(port, ptr, rust_vec_len, data_len)
+        }
+        5 => wire__crate__api__P2PEngine_list_interfaces_impl(port, ptr, rust_vec_len, data_len),
+        6 => wire__crate__api__P2PEngine_new_impl(port, ptr, rust_vec_len, data_len),
+        7 => wire__crate__api__P2PEngine_refresh_peer_impl(port, ptr, rust_vec_len, data_len),
+        8 => wire__crate__api__P2PEngine_scan_once_impl(port, ptr, rust_vec_len, data_len),
+        9 => wire__crate__api__P2PEngine_start_discovery_impl(port, ptr, rust_vec_len, data_len),
+        10 => wire__crate__api__P2PEngine_stop_discovery_impl(port, ptr, rust_vec_len, data_len),
+        11 => wire__crate__api__get_discovered_peers_impl(port, ptr, rust_vec_len, data_len),
+        12 => wire__crate__api__get_engine_impl(port, ptr, rust_vec_len, data_len),
+        13 => wire__crate__api__init_logging_impl(port, ptr, rust_vec_len, data_len),
+        14 => wire__crate__api__init_p2p_engine_impl(port, ptr, rust_vec_len, data_len),
+        15 => wire__crate__api__list_network_interfaces_impl(port, ptr, rust_vec_len, data_len),
+        16 => wire__crate__api__start_peer_discovery_impl(port, ptr, rust_vec_len, data_len),
+        17 => wire__crate__api__stop_peer_discovery_impl(port, ptr, rust_vec_len, data_len),
         _ => unreachable!(),
     }
 }
@@ -801,15 +1210,56 @@ impl flutter_rust_bridge::IntoIntoDart<FrbWrapper<P2PEngine>> for P2PEngine {
     }
 }
 
+// Codec=Dco (DartCObject based), see doc to use other codecs
+impl flutter_rust_bridge::IntoDart for crate::api::FlutterError {
+    fn into_dart(self) -> flutter_rust_bridge::for_generated::DartAbi {
+        [
+            self.code.into_into_dart().into_dart(),
+            self.message.into_into_dart().into_dart(),
+        ]
+        .into_dart()
+    }
+}
+impl flutter_rust_bridge::for_generated::IntoDartExceptPrimitive for crate::api::FlutterError {}
+impl flutter_rust_bridge::IntoIntoDart<crate::api::FlutterError> for crate::api::FlutterError {
+    fn into_into_dart(self) -> crate::api::FlutterError {
+        self
+    }
+}
+// Codec=Dco (DartCObject based), see doc to use other codecs
+impl flutter_rust_bridge::IntoDart for crate::api::FlutterInterface {
+    fn into_dart(self) -> flutter_rust_bridge::for_generated::DartAbi {
+        [
+            self.name.into_into_dart().into_dart(),
+            self.ip.into_into_dart().into_dart(),
+            self.is_loopback.into_into_dart().into_dart(),
+        ]
+        .into_dart()
+    }
+}
+impl flutter_rust_bridge::for_generated::IntoDartExceptPrimitive for crate::api::FlutterInterface {}
+impl flutter_rust_bridge::IntoIntoDart<crate::api::FlutterInterface>
+    for crate::api::FlutterInterface
+{
+    fn into_into_dart(self) -> crate::api::FlutterInterface {
+        self
+    }
+}
 // Codec=Dco (DartCObject based), see doc to use other codecs
 impl flutter_rust_bridge::IntoDart for crate::api::FlutterPeer {
     fn into_dart(self) -> flutter_rust_bridge::for_generated::DartAbi {
         [
             self.id.into_into_dart().into_dart(),
             self.name.into_into_dart().into_dart(),
+            self.hostname.into_into_dart().into_dart(),
             self.ip.into_into_dart().into_dart(),
+            self.addresses.into_into_dart().into_dart(),
+            self.address_pending.into_into_dart().into_dart(),
             self.port.into_into_dart().into_dart(),
+            self.service_type.into_into_dart().into_dart(),
             self.device_type.into_into_dart().into_dart(),
+            self.freshness.into_into_dart().into_dart(),
+            self.compatible.into_into_dart().into_dart(),
             self.properties.into_into_dart().into_dart(),
         ]
         .into_dart()
@@ -879,18 +1329,81 @@ impl SseEncode for String {
     }
 }
 
+impl SseEncode for Option<crate::api::FlutterPeer> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <bool>::sse_encode(self.is_some(), serializer);
+        if let Some(value) = self {
+            <crate::api::FlutterPeer>::sse_encode(value, serializer);
+        }
+    }
+}
+
+impl SseEncode for Option<u16> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <bool>::sse_encode(self.is_some(), serializer);
+        if let Some(value) = self {
+            <u16>::sse_encode(value, serializer);
+        }
+    }
+}
+
+impl SseEncode for crate::api::FlutterError {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <String>::sse_encode(self.code, serializer);
+        <String>::sse_encode(self.message, serializer);
+    }
+}
+
+impl SseEncode for crate::api::FlutterInterface {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <String>::sse_encode(self.name, serializer);
+        <String>::sse_encode(self.ip, serializer);
+        <bool>::sse_encode(self.is_loopback, serializer);
+    }
+}
+
 impl SseEncode for crate::api::FlutterPeer {
     // Codec=Sse (Serialization based), see doc to use other codecs
     fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
         <String>::sse_encode(self.id, serializer);
         <String>::sse_encode(self.name, serializer);
+        <String>::sse_encode(self.hostname, serializer);
         <String>::sse_encode(self.ip, serializer);
+        <Vec<String>>::sse_encode(self.addresses, serializer);
+        <bool>::sse_encode(self.address_pending, serializer);
         <u16>::sse_encode(self.port, serializer);
+        <String>::sse_encode(self.service_type, serializer);
         <String>::sse_encode(self.device_type, serializer);
+        <String>::sse_encode(self.freshness, serializer);
+        <bool>::sse_encode(self.compatible, serializer);
         <std::collections::HashMap<String, String>>::sse_encode(self.properties, serializer);
     }
 }
 
+impl SseEncode for Vec<String> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <i32>::sse_encode(self.len() as _, serializer);
+        for item in self {
+            <String>::sse_encode(item, serializer);
+        }
+    }
+}
+
+impl SseEncode for Vec<crate::api::FlutterInterface> {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
+        <i32>::sse_encode(self.len() as _, serializer);
+        for item in self {
+            <crate::api::FlutterInterface>::sse_encode(item, serializer);
+        }
+    }
+}
+
 impl SseEncode for Vec<crate::api::FlutterPeer> {
     // Codec=Sse (Serialization based), see doc to use other codecs
     fn sse_encode(self, serializer: &mut flutter_rust_bridge::for_generated::SseSerializer) {
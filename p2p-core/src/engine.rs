@@ -0,0 +1,207 @@
+// Rust-only additions to P2PEngine, kept out of `api` so flutter_rust_bridge doesn't export them
+use crate::api::{FlutterError, FlutterPeer, P2PEngine};
+use crate::peer_discovery::{recv_skipping_lag, DeviceType, PeerEvent};
+use futures::FutureExt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+// Peer changes delivered to a registered callback
+#[derive(Debug, Clone)]
+pub enum FlutterPeerEvent {
+    Discovered(FlutterPeer),
+    Lost(FlutterPeer),
+}
+
+pub(crate) type PeerCallback = Arc<dyn Fn(FlutterPeerEvent) + Send + Sync + 'static>;
+
+/// An open connection to a peer, shared by every caller of `P2PEngine::get_connection`
+pub type PeerConnection = Arc<Mutex<TcpStream>>;
+
+// Whether the other end still has the connection open, checked without blocking
+fn is_alive(connection: &PeerConnection) -> bool {
+    // Someone is using it right now, so it was alive a moment ago
+    let Ok(stream) = connection.try_lock() else {
+        return true;
+    };
+    let mut buf = [0u8; 1];
+    match stream.peek(&mut buf).now_or_never() {
+        // Nothing to read yet is the normal state of an idle connection
+        None => true,
+        Some(Ok(read)) => read > 0,
+        Some(Err(_)) => false,
+    }
+}
+
+impl FlutterPeer {
+    /// Parsed form of `device_type`
+    pub fn typed_device_type(&self) -> DeviceType {
+        self.device_type.parse().unwrap_or_else(|never| match never {})
+    }
+}
+
+impl P2PEngine {
+    /// How long `start_discovery` may take before giving up, 5 seconds by default
+    pub fn set_start_timeout(&mut self, start_timeout: Duration) {
+        self.start_timeout = start_timeout;
+    }
+
+    /// Register a callback fired for every discovered or lost peer
+    pub async fn set_peer_callback(&mut self, callback: impl Fn(FlutterPeerEvent) + Send + Sync + 'static) {
+        self.peer_callback = Some(Arc::new(callback));
+        self.restart_callback_task().await;
+    }
+
+    pub fn clear_peer_callback(&mut self) {
+        self.peer_callback = None;
+        if let Some(task) = self.callback_task.take() {
+            task.abort();
+        }
+    }
+
+    // Forward peer events from the running discovery to the registered callback
+    pub(crate) async fn restart_callback_task(&mut self) {
+        if let Some(task) = self.callback_task.take() {
+            task.abort();
+        }
+
+        let (Some(discovery), Some(callback)) = (&self.discovery, &self.peer_callback) else {
+            return;
+        };
+
+        let mut receiver = discovery.lock().await.subscribe();
+        let callback = callback.clone();
+
+        self.callback_task = Some(tokio::spawn(async move {
+            while let Some(event) = recv_skipping_lag(&mut receiver).await {
+                let event = match event {
+                    PeerEvent::PeerDiscovered(peer) => FlutterPeerEvent::Discovered(peer.into()),
+                    PeerEvent::PeerLost(peer) => FlutterPeerEvent::Lost(peer.into()),
+                    _ => continue,
+                };
+
+                // A panicking callback must not take down the event loop
+                if catch_unwind(AssertUnwindSafe(|| callback(event))).is_err() {
+                    error!("Peer callback panicked");
+                }
+            }
+        }));
+    }
+
+    // Drop pooled connections to peers as soon as they are lost
+    pub(crate) async fn restart_eviction_task(&mut self) {
+        if let Some(task) = self.eviction_task.take() {
+            task.abort();
+        }
+
+        let Some(discovery) = &self.discovery else {
+            return;
+        };
+
+        let mut receiver = discovery.lock().await.subscribe();
+        let connections = self.connections.clone();
+
+        self.eviction_task = Some(tokio::spawn(async move {
+            while let Some(event) = recv_skipping_lag(&mut receiver).await {
+                if let PeerEvent::PeerLost(peer) = event {
+                    if connections.lock().await.remove(&peer.id).is_some() {
+                        debug!("Closed pooled connection to lost peer {}", peer.name);
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Open connection to a discovered peer, reusing the pooled one while it's still open
+    ///
+    /// Connections are closed once the peer is lost or discovery stops.
+    pub async fn get_connection(&self, peer_id: String) -> Result<PeerConnection, FlutterError> {
+        let discovery = self.discovery.as_ref().ok_or_else(FlutterError::not_started)?;
+
+        {
+            let mut connections = self.connections.lock().await;
+            if let Some(connection) = connections.get(&peer_id) {
+                if is_alive(connection) {
+                    return Ok(connection.clone());
+                }
+                connections.remove(&peer_id);
+            }
+        }
+
+        // Connect without holding either lock, so a slow peer doesn't stall every other call
+        let discovery = discovery.lock().await.clone();
+        let peer = discovery.get_peer_by_id(&peer_id).await
+            .ok_or_else(|| FlutterError::new("peer_not_found", format!("No peer with ID {}", peer_id)))?;
+        let connection = Arc::new(Mutex::new(discovery.connect_to(&peer).await?));
+        self.connections.lock().await.insert(peer_id, connection.clone());
+
+        Ok(connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer_discovery::{DiscoveryConfig, PeerDiscovery};
+    use std::net::IpAddr;
+
+    fn listening_peer(id: &str, port: u16) -> mdns_sd::ServiceEvent {
+        let info = mdns_sd::ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            id,
+            &format!("{}.local.", id),
+            "127.0.0.1",
+            port,
+            &[(crate::INSTANCE_ID_PROPERTY, id)][..],
+        ).unwrap();
+        mdns_sd::ServiceEvent::ServiceResolved(info)
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // Keep every accepted connection open
+            let mut accepted = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.push(stream);
+            }
+        });
+
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("pool")
+            .test_mode(true)
+            .bind_ip(IpAddr::from([127, 0, 0, 1]))
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        discovery.inject_service_event(listening_peer("pool-peer", port)).unwrap();
+        discovery.wait_for_peer(|peer| peer.id == "pool-peer", Duration::from_secs(1)).await.unwrap();
+
+        let mut engine = P2PEngine::new();
+        assert_eq!(engine.get_connection("pool-peer".to_string()).await.unwrap_err().code, "not_started");
+        engine.discovery = Some(Arc::new(Mutex::new(discovery.clone())));
+        engine.restart_eviction_task().await;
+
+        let first = engine.get_connection("pool-peer".to_string()).await.unwrap();
+        let second = engine.get_connection("pool-peer".to_string()).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(engine.get_connection("unknown".to_string()).await.unwrap_err().code, "peer_not_found");
+
+        // Losing the peer closes its pooled connection
+        discovery.inject_service_event(mdns_sd::ServiceEvent::ServiceRemoved(
+            "_qopyapp._tcp.local.".to_string(),
+            "pool-peer._qopyapp._tcp.local.".to_string(),
+        )).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while engine.connections.lock().await.contains_key("pool-peer") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        engine.stop_discovery().await.unwrap();
+    }
+}
@@ -5,6 +5,9 @@ pub enum PeerDiscoveryError {
     #[error("mDNS service error: {0}")]
     MdnsError(String),
     
+    #[error("mDNS daemon failed to start: {0}")]
+    DaemonInitFailed(String),
+    
     #[error("Network interface error: {0}")]
     NetworkInterfaceError(String),
     
@@ -17,9 +20,18 @@ pub enum PeerDiscoveryError {
     #[error("Invalid service type: {0}")]
     InvalidServiceType(String),
     
+    #[error("Invalid device type: {0}")]
+    InvalidDeviceType(String),
+    
     #[error("Timeout waiting for discovery: {0}")]
     DiscoveryTimeout(String),
     
+    #[error("Connection failed: {0}")]
+    ConnectionFailed(String),
+    
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+    
     #[error("IO error: {0}")]
     IoError(String),
 }
@@ -35,3 +47,21 @@ impl From<std::io::Error> for PeerDiscoveryError {
         PeerDiscoveryError::IoError(err.to_string())
     }
 }
+
+impl From<serde_json::Error> for PeerDiscoveryError {
+    fn from(err: serde_json::Error) -> Self {
+        PeerDiscoveryError::SerializationError(err.to_string())
+    }
+}
+
+impl From<toml::de::Error> for PeerDiscoveryError {
+    fn from(err: toml::de::Error) -> Self {
+        PeerDiscoveryError::SerializationError(err.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for PeerDiscoveryError {
+    fn from(err: toml::ser::Error) -> Self {
+        PeerDiscoveryError::SerializationError(err.to_string())
+    }
+}
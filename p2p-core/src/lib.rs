@@ -2,7 +2,11 @@ mod bridge_generated; /* AUTO INJECTED BY flutter_rust_bridge. This line may not
 pub mod peer_discovery;
 pub mod error;
 pub mod api;
+pub mod engine;
+mod transport;
 
-pub use peer_discovery::{PeerDiscovery, DiscoveryConfig, PeerEvent, Peer, get_network_interfaces};
+pub use peer_discovery::{PeerDiscovery, DiscoveryConfig, DiscoveryConfigBuilder, IpPreference, PeerEvent, PeerChange, PeerDiff, diff_peers, PeerSort, PeerFilter, Peer, PeerSource, get_network_interfaces, get_usable_interfaces, IpFamily, validate_service_type, normalize_fullname, local_instance_id, recv_skipping_lag, CAPABILITIES_PROPERTY, DEVICE_TYPE_PROPERTY, INSTANCE_ID_PROPERTY, VERSION_PROPERTY, LIVENESS_RESPONSE, DeviceType, DeviceProfile, DiscoveryStats, DiscoverResult, Freshness};
 pub use error::PeerDiscoveryError;
-pub use api::{P2PEngine, FlutterPeer};
+pub use transport::{DiscoveryBackend, StaticBackend};
+pub use api::{P2PEngine, FlutterPeer, FlutterInterface, FlutterError};
+pub use engine::{FlutterPeerEvent, PeerConnection};
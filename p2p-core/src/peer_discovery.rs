@@ -1,416 +1,6012 @@
 use crate::error::PeerDiscoveryError;
+use crate::transport::{new_backend, DiscoveryBackend};
 use anyhow::Result;
-use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use mdns_sd::{ServiceEvent, ServiceInfo};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::net::IpAddr;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{broadcast, RwLock};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Notify, RwLock, Semaphore};
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
 use tokio::time::sleep;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
 use tracing::{debug, error, info, warn};
 
-#[cfg(not(target_os = "android"))]
-use get_if_addrs;
-
 /// Represents a discovered peer with its network information
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Peer {
+    /// Stable identity advertised via the `instance_id` TXT record, or the normalized fullname for peers without one
+    pub id: String,
     pub name: String,
+    /// Host advertised in the SRV record (e.g. "laptop.local."), empty when unavailable
+    #[serde(default)]
+    pub hostname: String,
+    /// Primary address, selected according to the configured `IpPreference`
     pub ip: IpAddr,
+    /// All resolved addresses, with the primary address first
+    pub addresses: Vec<IpAddr>,
+    /// Resolved without a usable address yet; `ip` is unspecified and `addresses` empty until one arrives
+    #[serde(default)]
+    pub address_pending: bool,
     pub port: u16,
     pub service_type: String,
+    /// TXT values decoded as UTF-8, invalid bytes replaced; see `raw_properties` for the exact data
     pub properties: HashMap<String, String>,
+    /// TXT values exactly as received, for binary data the lossy string map would mangle
+    #[serde(default)]
+    pub raw_properties: HashMap<String, Vec<u8>>,
+    /// Whether the advertised `version` meets `DiscoveryConfig::min_compatible_version`
+    #[serde(default = "default_compatible")]
+    pub compatible: bool,
+    /// Whether mDNS found the peer or it was added by hand
+    #[serde(default)]
+    pub source: PeerSource,
+    /// When the peer was last resolved
+    pub last_seen: SystemTime,
 }
 
-/// Configuration for the peer discovery service
-#[derive(Debug, Clone)]
-pub struct DiscoveryConfig {
-    pub service_type: String,
-    pub service_name: String,
-    pub port: u16,
-    pub properties: HashMap<String, String>,
-    pub discovery_timeout: Duration,
-    pub announce_interval: Duration,
+fn default_compatible() -> bool {
+    true
 }
 
-impl Default for DiscoveryConfig {
-    fn default() -> Self {
-        Self {
-            service_type: "_qopyapp._tcp.local.".to_string(),
-            service_name: "qopyapp-device".to_string(),
-            port: 8080,
-            properties: HashMap::new(),
-            discovery_timeout: Duration::from_secs(10),
-            announce_interval: Duration::from_secs(30),
-        }
-    }
+/// How a peer became known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PeerSource {
+    #[default]
+    Mdns,
+    /// Added through `add_manual_peer`, e.g. from a QR code or a typed address
+    Manual,
+    /// Loaded from `DiscoveryConfig::cache_path` and not seen again since, it may be gone
+    Cached,
 }
 
-/// Main peer discovery service that handles mDNS broadcasting and discovery
-pub struct PeerDiscovery {
-    daemon: ServiceDaemon,
-    config: DiscoveryConfig,
-    discovered_peers: Arc<RwLock<HashMap<String, Peer>>>,
-    peer_sender: broadcast::Sender<PeerEvent>,
-    is_running: Arc<RwLock<bool>>,
+/// TXT record key carrying the stable instance ID
+pub const INSTANCE_ID_PROPERTY: &str = "instance_id";
+
+/// Identifier generated once for this process, advertised so peers can track us across renames
+pub fn local_instance_id() -> &'static str {
+    static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    INSTANCE_ID.get_or_init(|| {
+        let mut hasher = RandomState::new().build_hasher();
+        std::process::id().hash(&mut hasher);
+        SystemTime::now().hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    })
 }
 
-impl Clone for PeerDiscovery {
-    fn clone(&self) -> Self {
-        Self {
-            daemon: self.daemon.clone(),
-            config: self.config.clone(),
-            discovered_peers: self.discovered_peers.clone(),
-            peer_sender: self.peer_sender.clone(),
-            is_running: self.is_running.clone(),
+/// TXT record key carrying the device type
+pub const DEVICE_TYPE_PROPERTY: &str = "device_type";
+
+/// Kind of device advertised in the `device_type` TXT record
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceType {
+    Desktop,
+    Laptop,
+    Phone,
+    Tablet,
+    Server,
+    /// Any other value, kept verbatim
+    Unknown(String),
+}
+
+impl FromStr for DeviceType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "desktop" => DeviceType::Desktop,
+            "laptop" => DeviceType::Laptop,
+            "phone" => DeviceType::Phone,
+            "tablet" => DeviceType::Tablet,
+            "server" => DeviceType::Server,
+            _ => DeviceType::Unknown(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceType::Desktop => write!(f, "desktop"),
+            DeviceType::Laptop => write!(f, "laptop"),
+            DeviceType::Phone => write!(f, "phone"),
+            DeviceType::Tablet => write!(f, "tablet"),
+            DeviceType::Server => write!(f, "server"),
+            DeviceType::Unknown(value) => write!(f, "{}", value),
         }
     }
 }
 
-/// Events that can be emitted by the peer discovery service
-#[derive(Debug, Clone)]
-pub enum PeerEvent {
-    PeerDiscovered(Peer),
-    PeerLost(Peer),
-    ServiceStarted,
-    ServiceStopped,
-    Error(PeerDiscoveryError),
+// Serialized as the TXT record value so configs read "laptop" rather than an enum tag
+impl Serialize for DeviceType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
 }
 
-impl PeerDiscovery {
-    /// Create a new peer discovery instance
-    pub fn new(config: DiscoveryConfig) -> Result<Self, PeerDiscoveryError> {
-        let daemon = ServiceDaemon::new()?;
-        let (peer_sender, _) = broadcast::channel(100);
-        
-        Ok(Self {
-            daemon,
-            config,
-            discovered_peers: Arc::new(RwLock::new(HashMap::new())),
-            peer_sender,
-            is_running: Arc::new(RwLock::new(false)),
-        })
+impl<'de> Deserialize<'de> for DeviceType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(value.parse().unwrap_or_else(|never| match never {}))
     }
+}
 
-    /// Start the peer discovery service
-    pub async fn start(&self) -> Result<(), PeerDiscoveryError> {
-        let mut is_running = self.is_running.write().await;
-        if *is_running {
-            return Ok(());
-        }
-        *is_running = true;
-        drop(is_running);
+/// How recently a peer was seen, a stand-in for signal strength since mDNS has none
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Freshness {
+    /// Seen within the last 30 seconds
+    Fresh,
+    /// Seen within the last 2 minutes
+    Recent,
+    Stale,
+}
 
-        info!("Starting peer discovery service");
-        
-        // Register our own service
-        self.register_service().await?;
-        
-        // Start discovery
-        self.start_discovery().await?;
-        
-        let _ = self.peer_sender.send(PeerEvent::ServiceStarted);
-        info!("Peer discovery service started successfully");
-        
-        Ok(())
+impl Freshness {
+    /// Bucket for a peer last seen `age` ago
+    pub fn from_age(age: Duration) -> Self {
+        if age < Duration::from_secs(30) {
+            Freshness::Fresh
+        } else if age < Duration::from_secs(120) {
+            Freshness::Recent
+        } else {
+            Freshness::Stale
+        }
     }
+}
 
-    /// Stop the peer discovery service
-    pub async fn stop(&self) -> Result<(), PeerDiscoveryError> {
-        let mut is_running = self.is_running.write().await;
-        if !*is_running {
-            return Ok(());
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Freshness::Fresh => write!(f, "fresh"),
+            Freshness::Recent => write!(f, "recent"),
+            Freshness::Stale => write!(f, "stale"),
         }
-        *is_running = false;
-        drop(is_running);
+    }
+}
 
-        info!("Stopping peer discovery service");
-        
-        // Unregister our service
-        if let Err(e) = self.daemon.unregister(&self.config.service_name) {
-            warn!("Failed to unregister service: {}", e);
+/// TXT record key carrying the comma-separated capability list
+pub const CAPABILITIES_PROPERTY: &str = "capabilities";
+
+/// TXT record key carrying the protocol version, e.g. "1.2.0"
+pub const VERSION_PROPERTY: &str = "version";
+
+/// What `serve_liveness` writes to every connection before closing it
+pub const LIVENESS_RESPONSE: &[u8] = b"qopyapp-alive\n";
+
+/// Split a comma-separated capability list, skipping empty entries
+fn parse_capabilities(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|capability| !capability.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Collect TXT key/value pairs as raw bytes, keys without a value are dropped
+fn raw_txt_properties<'a>(txt: impl IntoIterator<Item = (&'a str, Option<&'a [u8]>)>) -> HashMap<String, Vec<u8>> {
+    txt.into_iter()
+        .filter_map(|(key, val)| val.map(|val| (key.to_string(), val.to_vec())))
+        .collect()
+}
+
+/// Decode raw TXT values as UTF-8, replacing invalid bytes
+fn decode_txt_properties(raw: &HashMap<String, Vec<u8>>) -> HashMap<String, String> {
+    raw.iter()
+        .map(|(key, val)| (key.clone(), String::from_utf8_lossy(val).to_string()))
+        .collect()
+}
+
+/// Typed form of the well-known TXT properties, everything else is kept in `extra`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProfile {
+    /// Empty when not advertised
+    pub version: String,
+    pub device_type: DeviceType,
+    pub capabilities: Vec<String>,
+    pub extra: HashMap<String, String>,
+}
+
+impl DeviceProfile {
+    /// TXT properties for this profile, the typed fields win over same-named `extra` entries
+    pub fn to_properties(&self) -> HashMap<String, String> {
+        let mut properties = self.extra.clone();
+        if !self.version.is_empty() {
+            properties.insert(VERSION_PROPERTY.to_string(), self.version.clone());
         }
-        
-        // Clear discovered peers
-        {
-            let mut peers = self.discovered_peers.write().await;
-            peers.clear();
+        properties.insert(DEVICE_TYPE_PROPERTY.to_string(), self.device_type.to_string());
+        if !self.capabilities.is_empty() {
+            properties.insert(CAPABILITIES_PROPERTY.to_string(), self.capabilities.join(","));
         }
-        
-        let _ = self.peer_sender.send(PeerEvent::ServiceStopped);
-        info!("Peer discovery service stopped");
-        
-        Ok(())
+        properties
     }
 
-    /// Get a receiver for peer events
-    pub fn subscribe(&self) -> broadcast::Receiver<PeerEvent> {
-        self.peer_sender.subscribe()
+    /// Read a profile from TXT properties, a missing device type becomes `Unknown("unknown")`
+    pub fn from_properties(properties: &HashMap<String, String>) -> Self {
+        let mut extra = properties.clone();
+        let version = extra.remove(VERSION_PROPERTY).unwrap_or_default();
+        let device_type = match extra.remove(DEVICE_TYPE_PROPERTY) {
+            Some(value) => value.parse().unwrap_or_else(|never| match never {}),
+            None => DeviceType::Unknown("unknown".to_string()),
+        };
+        let capabilities = extra.remove(CAPABILITIES_PROPERTY)
+            .map(|value| parse_capabilities(&value))
+            .unwrap_or_default();
+
+        DeviceProfile {
+            version,
+            device_type,
+            capabilities,
+            extra,
+        }
     }
+}
 
-    /// Get all currently discovered peers
-    pub async fn get_peers(&self) -> Vec<Peer> {
-        let peers = self.discovered_peers.read().await;
-        peers.values().cloned().collect()
+/// Numeric components of a dotted version like "1.2.0" or "v2", `None` if any part isn't a number
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Whether `version` is at least `minimum`, missing trailing components count as zero
+fn version_at_least(version: &str, minimum: &str) -> bool {
+    let (Some(mut version), Some(mut minimum)) = (parse_version(version), parse_version(minimum)) else {
+        return false;
+    };
+    let len = version.len().max(minimum.len());
+    version.resize(len, 0);
+    minimum.resize(len, 0);
+    version >= minimum
+}
+
+impl Peer {
+    /// Parse a single peer from JSON
+    pub fn from_json(json: &str) -> Result<Peer, PeerDiscoveryError> {
+        Ok(serde_json::from_str(json)?)
     }
 
-    /// Get a specific peer by name
-    pub async fn get_peer(&self, name: &str) -> Option<Peer> {
-        let peers = self.discovered_peers.read().await;
-        peers.get(name).cloned()
+    /// Parse a JSON array of peers, as produced by `PeerDiscovery::peers_as_json`
+    pub fn list_from_json(json: &str) -> Result<Vec<Peer>, PeerDiscoveryError> {
+        Ok(serde_json::from_str(json)?)
     }
 
-    /// Discover peers with a timeout
-    pub async fn discover_peers(&self, timeout_duration: Option<Duration>) -> Result<Vec<Peer>, PeerDiscoveryError> {
-        let timeout_duration = timeout_duration.unwrap_or(self.config.discovery_timeout);
-        
-        info!("Starting peer discovery with timeout: {:?}", timeout_duration);
-        
-        // Start discovery if not already running
-        if !*self.is_running.read().await {
-            self.start().await?;
+    /// Device type advertised in the `device_type` TXT record
+    pub fn device_type(&self) -> DeviceType {
+        match self.properties.get(DEVICE_TYPE_PROPERTY) {
+            Some(value) => value.parse().unwrap_or_else(|never| match never {}),
+            None => DeviceType::Unknown("unknown".to_string()),
         }
-        
-        // Wait for discovery timeout
-        sleep(timeout_duration).await;
-        
-        let peers = self.get_peers().await;
-        info!("Discovered {} peers", peers.len());
-        
-        Ok(peers)
     }
 
-    /// Register our own service for other peers to discover
-    async fn register_service(&self) -> Result<(), PeerDiscoveryError> {
-        let service_info = ServiceInfo::new(
-            &self.config.service_type,
-            &self.config.service_name,
-            &format!("{}.local.", self.config.service_name),
-            self.config.ip_address().await?,
-            self.config.port,
-            None, // No properties for now
-        )?;
-        
-        self.daemon.register(service_info)?;
-        info!("Registered service: {} on port {}", self.config.service_name, self.config.port);
-        
-        Ok(())
+    /// Capabilities advertised in the `capabilities` TXT record
+    pub fn capabilities(&self) -> Vec<String> {
+        self.properties.get(CAPABILITIES_PROPERTY)
+            .map(|value| parse_capabilities(value))
+            .unwrap_or_default()
     }
 
-    /// Start discovering other peers
-    async fn start_discovery(&self) -> Result<(), PeerDiscoveryError> {
-        let daemon = self.daemon.clone();
-        let service_type = self.config.service_type.clone();
-        let discovered_peers = self.discovered_peers.clone();
-        let peer_sender = self.peer_sender.clone();
-        
-        tokio::spawn(async move {
-            let receiver = daemon.browse(&service_type).map_err(|e| {
-                error!("Failed to start browsing: {}", e);
-                PeerDiscoveryError::ServiceDiscoveryFailed(e.to_string())
-            })?;
-            
-            info!("Started browsing for service type: {}", service_type);
-            
-            while let Ok(event) = receiver.recv_async().await {
-                if let Err(e) = Self::handle_service_event(event, &discovered_peers, &peer_sender).await {
-                    error!("Error handling service event: {}", e);
-                    let _ = peer_sender.send(PeerEvent::Error(e));
-                }
-            }
-            
-            Ok::<(), PeerDiscoveryError>(())
-        });
-        
-        Ok(())
+    /// Typed view of the advertised properties
+    pub fn profile(&self) -> DeviceProfile {
+        DeviceProfile::from_properties(&self.properties)
     }
 
-    /// Handle incoming service events (peer discovered/lost)
-    async fn handle_service_event(
-        event: ServiceEvent,
-        discovered_peers: &Arc<RwLock<HashMap<String, Peer>>>,
-        peer_sender: &broadcast::Sender<PeerEvent>,
-    ) -> Result<(), PeerDiscoveryError> {
-        match event {
-            ServiceEvent::ServiceResolved(info) => {
-                let peer = Peer {
-                    name: info.get_fullname().to_string(),
-                    ip: info.get_addresses()
-                        .iter()
-                        .find(|addr| addr.is_ipv4())
-                        .copied()
-                        .ok_or_else(|| PeerDiscoveryError::NetworkInterfaceError("No IPv4 address found".to_string()))?,
-                    port: info.get_port(),
-                    service_type: info.get_type().to_string(),
-                    properties: info.get_properties().iter()
-                        .filter_map(|prop| {
-                            prop.val().map(|val| {
-                                (prop.key().to_string(), String::from_utf8_lossy(val).to_string())
-                            })
-                        })
-                        .collect(),
-                };
-                
-                debug!("Peer discovered: {:?}", peer);
-                
-                // Add to discovered peers
-                {
-                    let mut peers = discovered_peers.write().await;
-                    peers.insert(peer.name.clone(), peer.clone());
-                }
-                
-                let _ = peer_sender.send(PeerEvent::PeerDiscovered(peer));
-            }
-            ServiceEvent::ServiceRemoved(_, fullname) => {
-                debug!("Peer lost: {}", fullname);
-                
-                // Remove from discovered peers
-                let removed_peer = {
-                    let mut peers = discovered_peers.write().await;
-                    peers.remove(&fullname)
-                };
-                
-                if let Some(peer) = removed_peer {
-                    let _ = peer_sender.send(PeerEvent::PeerLost(peer));
-                }
-            }
-            _ => {
-                debug!("Unhandled service event: {:?}", event);
-            }
+    /// Whether the peer advertises the given capability
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities().iter().any(|c| c == capability)
+    }
+
+    /// How recently the peer was seen, based on `last_seen` which each answered re-query refreshes
+    pub fn freshness(&self) -> Freshness {
+        Freshness::from_age(self.last_seen.elapsed().unwrap_or_default())
+    }
+
+    /// Every address of the peer paired with its port, primary first
+    pub fn socket_addrs(&self) -> Vec<SocketAddr> {
+        if self.address_pending {
+            return Vec::new();
         }
-        
-        Ok(())
+        if self.addresses.is_empty() {
+            return vec![self.primary_socket_addr()];
+        }
+        self.addresses.iter()
+            .map(|ip| SocketAddr::new(*ip, self.port))
+            .collect()
     }
-}
 
-impl DiscoveryConfig {
-    /// Get the local IP address for service registration
-    async fn ip_address(&self) -> Result<IpAddr, PeerDiscoveryError> {
-        #[cfg(not(target_os = "android"))]
-        {
-            // Try to get the first available IPv4 address
-            for interface in get_if_addrs::get_if_addrs().map_err(|e| {
-                PeerDiscoveryError::NetworkInterfaceError(e.to_string())
-            })? {
-                if interface.is_loopback() {
-                    continue;
-                }
+    /// The primary address paired with the port
+    pub fn primary_socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
 
-                if let std::net::IpAddr::V4(ipv4) = interface.ip() {
-                    return Ok(IpAddr::V4(ipv4));
-                }
-            }
+    /// Instance part of the fullname, the service type suffix is the same for everyone
+    fn instance_name(&self) -> &str {
+        self.name.strip_suffix(self.service_type.as_str())
+            .map(|name| name.trim_end_matches('.'))
+            .unwrap_or(&self.name)
+    }
 
-            Err(PeerDiscoveryError::NetworkInterfaceError(
-                "No suitable network interface found".to_string()
-            ))
-        }
+    /// Whether both describe the same device, comparing only the stable `id` and `name`
+    pub fn same_identity(&self, other: &Peer) -> bool {
+        self.id == other.id && self.name == other.name
+    }
 
-        #[cfg(target_os = "android")]
-        {
-            // On Android, use a default IP or try to detect it differently
-            // For now, use a placeholder that will be replaced by actual IP
-            Ok(IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 100)))
-        }
+    /// Whether both carry the same advertisement, comparing everything except `last_seen`
+    pub fn content_eq(&self, other: &Peer) -> bool {
+        Peer { last_seen: other.last_seen, ..self.clone() } == *other
     }
 }
 
-/// Utility function to get all available network interfaces
-pub async fn get_network_interfaces() -> Result<Vec<NetworkInterface>, PeerDiscoveryError> {
-    let mut result = Vec::new();
+/// Which IP address family to use for registration and peer resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IpPreference {
+    Ipv4Only,
+    Ipv6Only,
+    #[default]
+    PreferIpv4,
+    PreferIpv6,
+}
 
-    #[cfg(not(target_os = "android"))]
-    {
-        let interfaces = get_if_addrs::get_if_addrs().map_err(|e| {
-            PeerDiscoveryError::NetworkInterfaceError(e.to_string())
-        })?;
-
-        for interface in interfaces {
-            result.push(NetworkInterface {
-                name: interface.name.clone(),
-                ip: interface.ip(),
-                is_loopback: interface.is_loopback(),
-            });
+impl IpPreference {
+    /// Whether an address is acceptable under this preference
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpPreference::Ipv4Only => ip.is_ipv4(),
+            IpPreference::Ipv6Only => ip.is_ipv6(),
+            IpPreference::PreferIpv4 | IpPreference::PreferIpv6 => true,
         }
     }
 
-    #[cfg(target_os = "android")]
+    /// Pick the best address from a set of candidates, link-local IPv6 only when nothing else is left
+    pub fn select<'a, I>(&self, addresses: I) -> Option<IpAddr>
+    where
+        I: IntoIterator<Item = &'a IpAddr>,
     {
-        // Return a default interface for Android
-        result.push(NetworkInterface {
-            name: "wlan0".to_string(),
-            ip: IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 100)),
-            is_loopback: false,
-        });
+        let mut candidates: Vec<IpAddr> = addresses.into_iter()
+            .copied()
+            .filter(|ip| self.allows(ip))
+            .collect();
+        // Link-local IPv6 needs a scope ID to be usable, rank it behind everything else
+        candidates.sort_by_key(|ip| ip.is_ipv6() && is_link_local(ip));
+
+        let preferred = match self {
+            IpPreference::Ipv4Only | IpPreference::PreferIpv4 => candidates.iter().find(|ip| ip.is_ipv4()),
+            IpPreference::Ipv6Only | IpPreference::PreferIpv6 => candidates.iter()
+                .find(|ip| ip.is_ipv6() && !is_link_local(ip)),
+        };
+
+        preferred.or_else(|| candidates.first()).copied()
     }
 
-    Ok(result)
+    /// The primary address, plus a routable address of the other family when both are allowed
+    fn dual_stack(&self, primary: IpAddr, candidates: &[IpAddr]) -> Vec<IpAddr> {
+        let mut addresses = vec![primary];
+        if matches!(self, IpPreference::PreferIpv4 | IpPreference::PreferIpv6) {
+            // Link-local IPv6 needs a scope ID to be usable, so it isn't worth advertising
+            let other = candidates.iter().find(|ip| match ip {
+                IpAddr::V4(_) => primary.is_ipv6(),
+                IpAddr::V6(_) => primary.is_ipv4() && !is_link_local(ip),
+            });
+            addresses.extend(other);
+        }
+        addresses
+    }
 }
 
-/// Represents a network interface
-#[derive(Debug, Clone)]
-pub struct NetworkInterface {
-    pub name: String,
-    pub ip: IpAddr,
-    pub is_loopback: bool,
+/// Ordering for `PeerDiscovery::get_peers_sorted`, ties are broken by peer id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PeerSort {
+    #[default]
+    ByName,
+    ByIp,
+    /// Most recently seen first
+    ByLastSeen,
+    ByDeviceType,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tokio::time::sleep;
+impl PeerSort {
+    fn compare(&self, a: &Peer, b: &Peer) -> std::cmp::Ordering {
+        let ordering = match self {
+            PeerSort::ByName => a.name.cmp(&b.name),
+            PeerSort::ByIp => a.ip.cmp(&b.ip),
+            PeerSort::ByLastSeen => b.last_seen.cmp(&a.last_seen),
+            PeerSort::ByDeviceType => a.device_type().to_string().cmp(&b.device_type().to_string())
+                .then_with(|| a.name.cmp(&b.name)),
+        };
 
-    #[tokio::test]
-    async fn test_peer_discovery_creation() {
-        let config = DiscoveryConfig::default();
-        let discovery = PeerDiscovery::new(config);
-        assert!(discovery.is_ok());
+        ordering.then_with(|| a.id.cmp(&b.id))
     }
+}
 
-    #[tokio::test]
-    async fn test_peer_discovery_start_stop() {
-        let config = DiscoveryConfig::default();
-        let discovery = PeerDiscovery::new(config).unwrap();
-        
-        // Start the service
-        assert!(discovery.start().await.is_ok());
-        
-        // Wait a bit
-        sleep(Duration::from_millis(100)).await;
+/// Which peers to admit, checked before a resolved peer is stored or reported
+///
+/// Name patterns match the instance name case-insensitively, `*` matches any run of characters.
+/// A peer must match every non-empty allow list and no deny entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerFilter {
+    pub allow_names: Vec<String>,
+    pub deny_names: Vec<String>,
+    pub allow_device_types: HashSet<DeviceType>,
+    pub deny_device_types: HashSet<DeviceType>,
+}
+
+impl PeerFilter {
+    pub fn allow_name(mut self, pattern: impl Into<String>) -> Self {
+        self.allow_names.push(pattern.into());
+        self
+    }
+
+    pub fn deny_name(mut self, pattern: impl Into<String>) -> Self {
+        self.deny_names.push(pattern.into());
+        self
+    }
+
+    pub fn allow_device_type(mut self, device_type: DeviceType) -> Self {
+        self.allow_device_types.insert(device_type);
+        self
+    }
+
+    pub fn deny_device_type(mut self, device_type: DeviceType) -> Self {
+        self.deny_device_types.insert(device_type);
+        self
+    }
+
+    /// Whether `peer` gets past this filter
+    pub fn admits(&self, peer: &Peer) -> bool {
+        let name = peer.instance_name();
+        let device_type = peer.device_type();
+
+        (self.allow_names.is_empty() || self.allow_names.iter().any(|pattern| wildcard_match(pattern, name)))
+            && (self.allow_device_types.is_empty() || self.allow_device_types.contains(&device_type))
+            && !self.deny_names.iter().any(|pattern| wildcard_match(pattern, name))
+            && !self.deny_device_types.contains(&device_type)
+    }
+}
+
+/// Configuration for the peer discovery service
+///
+/// Serializes with durations as (fractional) seconds, e.g. `discovery_timeout = 10.0` in TOML.
+/// Missing fields take their defaults, except optional fields which are `None` when missing
+/// (TOML can't spell `None`); `backend` is never serialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    pub service_type: String,
+    pub service_name: String,
+    pub port: u16,
+    pub properties: HashMap<String, String>,
+    #[serde(with = "duration_secs")]
+    pub discovery_timeout: Duration,
+    /// How often the service is re-registered while running, never more than once a second
+    #[serde(with = "duration_secs")]
+    pub announce_interval: Duration,
+    pub ip_preference: IpPreference,
+    /// Peers not seen within this window are pruned; `None` (the default) disables pruning.
+    /// The network is queried again before pruning, so quiet peers that are still online stay.
+    #[serde(default, with = "option_duration_secs")]
+    pub peer_ttl: Option<Duration>,
+    /// Stable identity advertised to peers, defaults to `local_instance_id()`
+    pub instance_id: String,
+    /// Address to advertise instead of the auto-detected one
+    #[serde(default)]
+    pub bind_ip: Option<IpAddr>,
+    /// Return from `discover_peers` once this many peers are known; `None` waits the full timeout
+    #[serde(default)]
+    pub early_exit_after: Option<usize>,
+    /// Watch the local interfaces and re-register when our address changes
+    pub auto_reannounce_on_network_change: bool,
+    /// Capacity of the broadcast channel behind `subscribe()`
+    pub event_buffer_size: usize,
+    /// Timeout for each connection attempt made by `connect_to`
+    #[serde(with = "duration_secs")]
+    pub connect_timeout: Duration,
+    /// Fallback ports to try when `port` is already taken
+    #[serde(default)]
+    pub port_range: Option<RangeInclusive<u16>>,
+    /// Keep our own service in the discovered peers
+    pub include_self: bool,
+    /// Random spread applied to each re-announce so devices don't announce in lockstep
+    #[serde(with = "duration_secs")]
+    pub announce_jitter: Duration,
+    /// DNS-SD subtypes (e.g. "filesharing"); when set only peers registering one of them are browsed.
+    /// The daemon advertises a single subtype per service, so only the first one is registered.
+    pub subtypes: Vec<String>,
+    /// Longest timeout `discover_peers` accepts, longer ones are rejected
+    #[serde(with = "duration_secs")]
+    pub max_discovery_timeout: Duration,
+    /// Advertise the address of this interface (e.g. "eth0") instead of auto-detecting one
+    #[serde(default)]
+    pub interface_name: Option<String>,
+    /// How many recent events `recent_events` keeps, 0 disables the history
+    pub event_history_size: usize,
+    /// Browse again at this interval so the daemon sends fresh queries; `None` (0 in TOML) leaves the querying to mdns-sd.
+    /// Every answer refreshes `Peer::last_seen`, the default keeps online peers `Freshness::Fresh`.
+    #[serde(default = "default_query_interval", with = "zero_is_none_secs")]
+    pub query_interval: Option<Duration>,
+    /// Use an in-memory transport instead of the mDNS daemon, nothing reaches the network.
+    /// Peers then only appear through `inject_service_event`.
+    pub test_mode: bool,
+    /// Only multicast over the interfaces with these addresses; empty lets the daemon use all of them
+    pub multicast_interfaces: Vec<IpAddr>,
+    /// Emit `PeerEvent::ScanProgress` at this interval while `discover_peers` runs; `None` emits none
+    #[serde(default, with = "option_duration_secs")]
+    pub scan_progress_interval: Option<Duration>,
+    /// Peers this filter doesn't admit are neither stored nor reported
+    #[serde(default)]
+    pub peer_filter: Option<PeerFilter>,
+    /// Most connection attempts `connect_to` makes at once, shared by batch checks like `get_reachable_peers`
+    pub max_concurrent_connections: usize,
+    /// Stop the service when no peer is discovered or updated for this long; `None` runs until stopped
+    #[serde(default, with = "option_duration_secs")]
+    pub idle_timeout: Option<Duration>,
+    /// Peers advertising an older or unparseable `version` are marked incompatible; `None` accepts all
+    #[serde(default)]
+    pub min_compatible_version: Option<String>,
+    /// Leave incompatible peers out entirely instead of only marking them
+    pub drop_incompatible_peers: bool,
+    /// Typed base for the advertised properties, entries in `properties` override it
+    #[serde(default)]
+    pub profile: Option<DeviceProfile>,
+    /// Register and browse through this instead of mDNS, takes precedence over `test_mode`
+    #[serde(skip)]
+    pub backend: Option<Arc<dyn DiscoveryBackend>>,
+    /// Device types we may advertise, catching typos like "laptp" in `new`; `None` allows any
+    #[serde(default)]
+    pub allowed_device_types: Option<HashSet<String>>,
+    /// Emit at most one `PeerUpdated` per peer within this window, later changes are coalesced
+    /// into one update at its end; `None` reports every change right away
+    #[serde(default, with = "option_duration_secs")]
+    pub update_debounce: Option<Duration>,
+    /// File the peer list is saved to while running and on `stop()`, and loaded from on `start()`
+    /// so the last known peers show up before the first scan completes; `None` keeps nothing
+    #[serde(default)]
+    pub cache_path: Option<PathBuf>,
+    /// Cached peers last seen longer ago than this are neither loaded nor kept
+    #[serde(with = "duration_secs")]
+    pub cache_max_age: Duration,
+    /// Keep our registration this long after `stop()`, so a `start()` within it resumes without
+    /// re-registering or emitting `ServiceStopped`/`ServiceStarted`; `None` tears down right away
+    #[serde(default, with = "option_duration_secs")]
+    pub restart_grace: Option<Duration>,
+    /// Most peers kept at once, a new peer evicts the least recently seen one; `None` is unbounded.
+    /// Manual peers count towards the limit but are never evicted.
+    #[serde(default)]
+    pub max_peers: Option<usize>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            service_type: "_qopyapp._tcp.local.".to_string(),
+            service_name: "qopyapp-device".to_string(),
+            port: 8080,
+            properties: HashMap::new(),
+            discovery_timeout: Duration::from_secs(10),
+            announce_interval: Duration::from_secs(30),
+            ip_preference: IpPreference::default(),
+            peer_ttl: None,
+            instance_id: local_instance_id().to_string(),
+            bind_ip: None,
+            early_exit_after: None,
+            auto_reannounce_on_network_change: false,
+            event_buffer_size: 100,
+            connect_timeout: Duration::from_secs(3),
+            port_range: None,
+            include_self: false,
+            announce_jitter: Duration::ZERO,
+            subtypes: Vec::new(),
+            max_discovery_timeout: Duration::from_secs(300),
+            interface_name: None,
+            event_history_size: 100,
+            query_interval: default_query_interval(),
+            test_mode: false,
+            multicast_interfaces: Vec::new(),
+            scan_progress_interval: None,
+            peer_filter: None,
+            max_concurrent_connections: 16,
+            idle_timeout: None,
+            min_compatible_version: None,
+            drop_incompatible_peers: false,
+            profile: None,
+            backend: None,
+            allowed_device_types: None,
+            update_debounce: None,
+            cache_path: None,
+            cache_max_age: Duration::from_secs(24 * 60 * 60),
+            restart_grace: None,
+            max_peers: None,
+        }
+    }
+}
+
+fn default_query_interval() -> Option<Duration> {
+    // Well inside the 30 seconds a peer counts as fresh
+    Some(Duration::from_secs(20))
+}
+
+impl DiscoveryConfig {
+    /// Create a builder that falls back to `DiscoveryConfig::default()` for unset fields
+    pub fn builder() -> DiscoveryConfigBuilder {
+        DiscoveryConfigBuilder::default()
+    }
+
+    /// Parse a config from TOML, fields left out keep their defaults
+    pub fn from_toml_str(toml: &str) -> Result<Self, PeerDiscoveryError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Render this config as TOML, unset optional fields are left out
+    pub fn to_toml_string(&self) -> Result<String, PeerDiscoveryError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Advertise the given device type in the `device_type` TXT record
+    pub fn with_device_type(mut self, device_type: DeviceType) -> Self {
+        self.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), device_type.to_string());
+        self
+    }
+
+    /// Advertise the given capabilities in the `capabilities` TXT record
+    pub fn with_capabilities(mut self, capabilities: &[&str]) -> Self {
+        self.properties.insert(CAPABILITIES_PROPERTY.to_string(), capabilities.join(","));
+        self
+    }
+
+    /// Advertise `ip` instead of auto-detecting a local address
+    pub fn with_bind_ip(mut self, ip: IpAddr) -> Self {
+        self.bind_ip = Some(ip);
+        self
+    }
+}
+
+/// Chainable builder for `DiscoveryConfig`
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryConfigBuilder {
+    config: DiscoveryConfig,
+}
+
+impl DiscoveryConfigBuilder {
+    pub fn service_type(mut self, service_type: impl Into<String>) -> Self {
+        self.config.service_type = service_type.into();
+        self
+    }
+
+    pub fn service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.config.service_name = service_name.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Add a single TXT property, replacing any existing value for the key
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.properties.insert(key.into(), value.into());
+        self
+    }
+
+    /// Replace all TXT properties
+    pub fn properties(mut self, properties: HashMap<String, String>) -> Self {
+        self.config.properties = properties;
+        self
+    }
+
+    pub fn discovery_timeout(mut self, discovery_timeout: Duration) -> Self {
+        self.config.discovery_timeout = discovery_timeout;
+        self
+    }
+
+    pub fn announce_interval(mut self, announce_interval: Duration) -> Self {
+        self.config.announce_interval = announce_interval;
+        self
+    }
+
+    pub fn ip_preference(mut self, ip_preference: IpPreference) -> Self {
+        self.config.ip_preference = ip_preference;
+        self
+    }
+
+    pub fn peer_ttl(mut self, peer_ttl: Option<Duration>) -> Self {
+        self.config.peer_ttl = peer_ttl;
+        self
+    }
+
+    pub fn instance_id(mut self, instance_id: impl Into<String>) -> Self {
+        self.config.instance_id = instance_id.into();
+        self
+    }
+
+    pub fn bind_ip(mut self, bind_ip: IpAddr) -> Self {
+        self.config.bind_ip = Some(bind_ip);
+        self
+    }
+
+    pub fn early_exit_after(mut self, early_exit_after: Option<usize>) -> Self {
+        self.config.early_exit_after = early_exit_after;
+        self
+    }
+
+    pub fn auto_reannounce_on_network_change(mut self, enabled: bool) -> Self {
+        self.config.auto_reannounce_on_network_change = enabled;
+        self
+    }
+
+    pub fn event_buffer_size(mut self, event_buffer_size: usize) -> Self {
+        self.config.event_buffer_size = event_buffer_size;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.config.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn port_range(mut self, port_range: RangeInclusive<u16>) -> Self {
+        self.config.port_range = Some(port_range);
+        self
+    }
+
+    pub fn include_self(mut self, include_self: bool) -> Self {
+        self.config.include_self = include_self;
+        self
+    }
+
+    pub fn announce_jitter(mut self, announce_jitter: Duration) -> Self {
+        self.config.announce_jitter = announce_jitter;
+        self
+    }
+
+    pub fn subtype(mut self, subtype: impl Into<String>) -> Self {
+        self.config.subtypes.push(subtype.into());
+        self
+    }
+
+    pub fn max_discovery_timeout(mut self, max_discovery_timeout: Duration) -> Self {
+        self.config.max_discovery_timeout = max_discovery_timeout;
+        self
+    }
+
+    pub fn interface_name(mut self, interface_name: impl Into<String>) -> Self {
+        self.config.interface_name = Some(interface_name.into());
+        self
+    }
+
+    pub fn event_history_size(mut self, event_history_size: usize) -> Self {
+        self.config.event_history_size = event_history_size;
+        self
+    }
+
+    pub fn query_interval(mut self, query_interval: Option<Duration>) -> Self {
+        self.config.query_interval = query_interval;
+        self
+    }
+
+    pub fn test_mode(mut self, test_mode: bool) -> Self {
+        self.config.test_mode = test_mode;
+        self
+    }
+
+    pub fn multicast_interface(mut self, ip: IpAddr) -> Self {
+        self.config.multicast_interfaces.push(ip);
+        self
+    }
+
+    pub fn scan_progress_interval(mut self, scan_progress_interval: Option<Duration>) -> Self {
+        self.config.scan_progress_interval = scan_progress_interval;
+        self
+    }
+
+    pub fn peer_filter(mut self, peer_filter: PeerFilter) -> Self {
+        self.config.peer_filter = Some(peer_filter);
+        self
+    }
+
+    pub fn max_concurrent_connections(mut self, max_concurrent_connections: usize) -> Self {
+        self.config.max_concurrent_connections = max_concurrent_connections;
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.config.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn min_compatible_version(mut self, min_compatible_version: impl Into<String>) -> Self {
+        self.config.min_compatible_version = Some(min_compatible_version.into());
+        self
+    }
+
+    pub fn drop_incompatible_peers(mut self, drop_incompatible_peers: bool) -> Self {
+        self.config.drop_incompatible_peers = drop_incompatible_peers;
+        self
+    }
+
+    pub fn profile(mut self, profile: DeviceProfile) -> Self {
+        self.config.profile = Some(profile);
+        self
+    }
+
+    pub fn backend(mut self, backend: Arc<dyn DiscoveryBackend>) -> Self {
+        self.config.backend = Some(backend);
+        self
+    }
+
+    pub fn allowed_device_types<I, S>(mut self, allowed_device_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.allowed_device_types = Some(allowed_device_types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn update_debounce(mut self, update_debounce: Option<Duration>) -> Self {
+        self.config.update_debounce = update_debounce;
+        self
+    }
+
+    pub fn cache_path(mut self, cache_path: impl Into<PathBuf>) -> Self {
+        self.config.cache_path = Some(cache_path.into());
+        self
+    }
+
+    pub fn cache_max_age(mut self, cache_max_age: Duration) -> Self {
+        self.config.cache_max_age = cache_max_age;
+        self
+    }
+
+    pub fn restart_grace(mut self, restart_grace: Option<Duration>) -> Self {
+        self.config.restart_grace = restart_grace;
+        self
+    }
+
+    pub fn max_peers(mut self, max_peers: usize) -> Self {
+        self.config.max_peers = Some(max_peers);
+        self
+    }
+
+    pub fn build(self) -> DiscoveryConfig {
+        self.config
+    }
+}
+
+/// Serde adapter storing a `Duration` as fractional seconds
+mod duration_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// `duration_secs` for optional durations, `None` is left out
+mod option_duration_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// `option_duration_secs` for fields defaulting to `Some`, where a left out `None` would not survive a round trip
+mod zero_is_none_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.unwrap_or_default().as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        let duration = Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(serde::de::Error::custom)?;
+        Ok(Some(duration).filter(|duration| !duration.is_zero()))
+    }
+}
+
+/// Full DNS-SD name for a subtype of `service_type`, e.g. "_filesharing._sub._qopyapp._tcp.local."
+fn subtype_service_type(subtype: &str, service_type: &str) -> String {
+    format!("_{}._sub.{}", subtype.trim_start_matches('_'), service_type)
+}
+
+/// ASCII case-insensitive substring check that doesn't allocate
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    needle.is_empty() || haystack.as_bytes()
+        .windows(needle.len())
+        .any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// ASCII case-insensitive match where `*` in `pattern` stands for any run of characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let text = text.to_ascii_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let [first, middle @ .., last] = parts.as_slice() else {
+        // No '*' at all
+        return pattern == text;
+    };
+
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// How often the local interfaces are checked when `auto_reannounce_on_network_change` is set
+const NETWORK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// First and longest wait before browsing again after the daemon failed us
+const BROWSE_RETRY_INITIAL: Duration = Duration::from_millis(500);
+const BROWSE_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// DNS-SD meta-query type, answered with every service type on the network
+const META_SERVICE_TYPE: &str = "_services._dns-sd._udp.local.";
+
+/// How long peers get to answer the query sent before a pruning sweep
+const PRUNE_REQUERY_GRACE: Duration = Duration::from_secs(3);
+
+/// Shortest wait between re-announces, however small `announce_interval` and jitter make it
+const MIN_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the peer list is written to `cache_path` while running
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Random 64-bit value for jitter and name suffixes
+fn random_u64() -> u64 {
+    // A freshly keyed hasher is a cheap source of randomness that needs no extra dependency
+    RandomState::new().build_hasher().finish()
+}
+
+/// Spread `interval` uniformly over `interval ± jitter`
+fn jittered_interval(interval: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return interval;
+    }
+
+    let random = random_u64();
+    // Clamped at zero the window shrinks, it must never reach past interval + jitter
+    let lowest = interval.saturating_sub(jitter);
+    let spread = u64::try_from((interval + jitter - lowest).as_nanos()).unwrap_or(u64::MAX - 1);
+    let offset = Duration::from_nanos(random % spread.saturating_add(1));
+
+    lowest + offset
+}
+
+/// Service fullnames registered by running discovery instances in this process
+fn claimed_service_names() -> &'static Mutex<HashSet<String>> {
+    static CLAIMED_NAMES: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    CLAIMED_NAMES.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Ports claimed by running discovery instances in this process
+fn claimed_ports() -> &'static Mutex<HashSet<u16>> {
+    static CLAIMED_PORTS: OnceLock<Mutex<HashSet<u16>>> = OnceLock::new();
+    CLAIMED_PORTS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Aborts background tasks and shuts the transport down once the last user-held handle is dropped
+struct ShutdownGuard {
+    tasks: Arc<Mutex<Vec<AbortHandle>>>,
+    transport: Arc<Mutex<Option<Arc<dyn DiscoveryBackend>>>>,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            for task in tasks.drain(..) {
+                task.abort();
+            }
+        }
+
+        let transport = self.transport.lock().ok().and_then(|mut transport| transport.take());
+        // Shutting down is async, without a runtime left the daemon goes when the process does
+        if let (Some(transport), Ok(runtime)) = (transport, tokio::runtime::Handle::try_current()) {
+            runtime.spawn(async move {
+                if let Err(e) = transport.shutdown().await {
+                    warn!("Failed to shut down the mDNS daemon: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Debounce state for one peer's `PeerUpdated` events
+struct UpdateWindow {
+    emitted_at: Instant,
+    // A coalesced update is scheduled for the end of the window
+    pending: bool,
+}
+
+/// Main peer discovery service that handles mDNS broadcasting and discovery
+pub struct PeerDiscovery {
+    // None once stop() has shut the daemon down, start() brings up a new one
+    transport: Arc<Mutex<Option<Arc<dyn DiscoveryBackend>>>>,
+    config: DiscoveryConfig,
+    discovered_peers: Arc<RwLock<HashMap<String, Peer>>>,
+    peer_sender: EventSender,
+    is_running: Arc<RwLock<bool>>,
+    // Running without mDNS because no interface can multicast, see `is_manual_only`
+    manual_only: Arc<RwLock<bool>>,
+    // Teardown deferred by `restart_grace`, a start() claiming it resumes instead
+    pending_teardown: Arc<Mutex<Option<AbortHandle>>>,
+    browse_types: Arc<RwLock<Vec<String>>>,
+    tasks: Arc<Mutex<Vec<AbortHandle>>>,
+    registered_ip: Arc<RwLock<Option<IpAddr>>>,
+    // Exact fullname the transport registered, which is what unregistering needs
+    registered_fullname: Arc<RwLock<Option<String>>>,
+    port: Arc<RwLock<u16>>,
+    service_name: Arc<Mutex<String>>,
+    properties: Arc<RwLock<HashMap<String, String>>>,
+    // Wakes the browse tasks to query again, see `refresh_peer`
+    requery: Arc<Notify>,
+    // Bounds concurrent `connect_to` calls to `max_concurrent_connections`
+    connection_permits: Arc<Semaphore>,
+    // Per-peer `PeerUpdated` windows, only used with `update_debounce`
+    update_windows: Arc<Mutex<HashMap<String, UpdateWindow>>>,
+    // None for the copies held by our own background tasks
+    shutdown: Option<Arc<ShutdownGuard>>,
+}
+
+impl Clone for PeerDiscovery {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            config: self.config.clone(),
+            discovered_peers: self.discovered_peers.clone(),
+            peer_sender: self.peer_sender.clone(),
+            is_running: self.is_running.clone(),
+            manual_only: self.manual_only.clone(),
+            pending_teardown: self.pending_teardown.clone(),
+            browse_types: self.browse_types.clone(),
+            tasks: self.tasks.clone(),
+            registered_ip: self.registered_ip.clone(),
+            registered_fullname: self.registered_fullname.clone(),
+            port: self.port.clone(),
+            service_name: self.service_name.clone(),
+            properties: self.properties.clone(),
+            requery: self.requery.clone(),
+            connection_permits: self.connection_permits.clone(),
+            update_windows: self.update_windows.clone(),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+}
+
+/// Counters describing what the discovery service has seen so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveryStats {
+    /// Peers discovered with a usable address
+    pub discovered: u64,
+    /// Peers discovered without a usable address yet, see `Peer::address_pending`
+    pub pending: u64,
+    pub updated: u64,
+    pub lost: u64,
+    pub errors: u64,
+    pub current_count: usize,
+}
+
+/// Outcome of a `discover_peers_detailed` scan
+#[derive(Debug, Clone)]
+pub struct DiscoverResult {
+    pub peers: Vec<Peer>,
+    /// How long the scan actually took
+    pub elapsed: Duration,
+    /// Whether the scan ran until the timeout instead of exiting early
+    pub timed_out: bool,
+    /// Events received while scanning, including our own ServiceStarted
+    pub events_seen: usize,
+}
+
+/// Running totals of emitted peer events
+#[derive(Default)]
+struct EventCounters {
+    discovered: AtomicU64,
+    pending: AtomicU64,
+    updated: AtomicU64,
+    lost: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl EventCounters {
+    fn record(&self, event: &PeerEvent) {
+        let counter = match event {
+            PeerEvent::PeerDiscovered(peer) if peer.address_pending => &self.pending,
+            PeerEvent::PeerDiscovered(_) => &self.discovered,
+            PeerEvent::PeerUpdated(_) => &self.updated,
+            PeerEvent::PeerLost(_) => &self.lost,
+            PeerEvent::Error(_) => &self.errors,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Fan-out for peer events: a bounded broadcast channel plus lossless per-subscriber queues
+#[derive(Clone)]
+struct EventSender {
+    broadcast: broadcast::Sender<PeerEvent>,
+    unbounded: Arc<Mutex<Vec<mpsc::UnboundedSender<PeerEvent>>>>,
+    counters: Arc<EventCounters>,
+    history: Arc<Mutex<VecDeque<(Instant, PeerEvent)>>>,
+    history_size: usize,
+}
+
+impl EventSender {
+    fn new(capacity: usize, history_size: usize) -> Self {
+        let (broadcast, _) = broadcast::channel(capacity.max(1));
+        Self {
+            broadcast,
+            unbounded: Arc::new(Mutex::new(Vec::new())),
+            counters: Arc::new(EventCounters::default()),
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(history_size))),
+            history_size,
+        }
+    }
+
+    /// The last `max` events, oldest first
+    fn recent(&self, max: usize) -> Vec<(Instant, PeerEvent)> {
+        let history = self.history.lock().unwrap();
+        history.iter()
+            .skip(history.len().saturating_sub(max))
+            .cloned()
+            .collect()
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<PeerEvent> {
+        self.broadcast.subscribe()
+    }
+
+    fn subscribe_unbounded(&self) -> mpsc::UnboundedReceiver<PeerEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.unbounded.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Deliver an event to every subscriber, forgetting unbounded ones that hung up.
+    /// Returns whether anyone received it.
+    fn send(&self, event: PeerEvent) -> bool {
+        self.counters.record(&event);
+        if self.history_size > 0 {
+            let mut history = self.history.lock().unwrap();
+            if history.len() == self.history_size {
+                history.pop_front();
+            }
+            history.push_back((Instant::now(), event.clone()));
+        }
+        let queued = {
+            let mut unbounded = self.unbounded.lock().unwrap();
+            unbounded.retain(|sender| sender.send(event.clone()).is_ok());
+            !unbounded.is_empty()
+        };
+        self.broadcast.send(event).is_ok() || queued
+    }
+}
+
+/// Events that can be emitted by the peer discovery service
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    PeerDiscovered(Peer),
+    /// A known peer was resolved again with different data (address, port, properties)
+    PeerUpdated(Peer),
+    PeerLost(Peer),
+    ServiceStarted,
+    ServiceStopped,
+    /// Our advertised address changed and the service was re-registered
+    LocalAddressChanged { previous: IpAddr, current: IpAddr },
+    /// Our advertised properties changed and the service was re-registered
+    LocalPropertiesChanged(HashMap<String, String>),
+    /// A `discover_peers` scan is still running, see `DiscoveryConfig::scan_progress_interval`
+    ScanProgress { elapsed: Duration, peers_found: usize },
+    Error(PeerDiscoveryError),
+}
+
+/// Wait for the next tick of an optional interval, forever when there is none
+async fn next_tick(ticker: Option<&mut tokio::time::Interval>) {
+    match ticker {
+        Some(ticker) => { ticker.tick().await; }
+        None => std::future::pending::<()>().await,
+    }
+}
+
+/// Receive the next event, logging and skipping over any lost to lag; `None` once the channel closes
+pub async fn recv_skipping_lag(receiver: &mut broadcast::Receiver<PeerEvent>) -> Option<PeerEvent> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Event receiver lagged behind, skipped {} events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Peer set changes only, see `PeerDiscovery::subscribe_peer_changes`
+#[derive(Debug, Clone)]
+pub enum PeerChange {
+    Added(Peer),
+    Removed(Peer),
+}
+
+/// What changed between two peer scans, see `diff_peers`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerDiff {
+    /// Only in the later scan
+    pub added: Vec<Peer>,
+    /// Only in the earlier scan
+    pub removed: Vec<Peer>,
+    /// In both but advertising something different, as seen in the later scan
+    pub changed: Vec<Peer>,
+}
+
+impl PeerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare two scans by peer ID, for callers polling `get_peers` instead of subscribing
+///
+/// Differences in `last_seen` alone don't count as a change.
+pub fn diff_peers(before: &[Peer], after: &[Peer]) -> PeerDiff {
+    let before_by_id: HashMap<&str, &Peer> = before.iter().map(|peer| (peer.id.as_str(), peer)).collect();
+    let after_ids: HashSet<&str> = after.iter().map(|peer| peer.id.as_str()).collect();
+    
+    let mut diff = PeerDiff::default();
+    for peer in after {
+        match before_by_id.get(peer.id.as_str()) {
+            None => diff.added.push(peer.clone()),
+            Some(previous) if !previous.content_eq(peer) => diff.changed.push(peer.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before.iter()
+        .filter(|peer| !after_ids.contains(peer.id.as_str()))
+        .cloned()
+        .collect();
+    diff
+}
+
+impl PeerDiscovery {
+    /// Create a new peer discovery instance
+    pub fn new(config: DiscoveryConfig) -> Result<Self, PeerDiscoveryError> {
+        validate_service_type(&config.service_type)?;
+        config.validate_multicast_interfaces()?;
+        config.validate_device_type()?;
+
+        let transport = new_backend(&config)?;
+        let peer_sender = EventSender::new(config.event_buffer_size, config.event_history_size);
+        let browse_types = if config.subtypes.is_empty() {
+            vec![config.service_type.clone()]
+        } else {
+            config.subtypes.iter()
+                .map(|subtype| subtype_service_type(subtype, &config.service_type))
+                .collect()
+        };
+        let port = config.port;
+        let service_name = config.service_name.clone();
+        let mut properties = config.profile.as_ref()
+            .map(DeviceProfile::to_properties)
+            .unwrap_or_default();
+        properties.extend(config.properties.clone());
+        // A limit of zero would block every connection forever
+        let max_concurrent_connections = config.max_concurrent_connections.max(1);
+        let tasks = Arc::new(Mutex::new(Vec::new()));
+        let transport = Arc::new(Mutex::new(Some(transport)));
+        
+        Ok(Self {
+            transport: transport.clone(),
+            config,
+            browse_types: Arc::new(RwLock::new(browse_types)),
+            discovered_peers: Arc::new(RwLock::new(HashMap::new())),
+            peer_sender,
+            is_running: Arc::new(RwLock::new(false)),
+            manual_only: Arc::new(RwLock::new(false)),
+            pending_teardown: Arc::new(Mutex::new(None)),
+            shutdown: Some(Arc::new(ShutdownGuard { tasks: tasks.clone(), transport })),
+            tasks,
+            registered_ip: Arc::new(RwLock::new(None)),
+            registered_fullname: Arc::new(RwLock::new(None)),
+            port: Arc::new(RwLock::new(port)),
+            service_name: Arc::new(Mutex::new(service_name)),
+            properties: Arc::new(RwLock::new(properties)),
+            requery: Arc::new(Notify::new()),
+            connection_permits: Arc::new(Semaphore::new(max_concurrent_connections)),
+            update_windows: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Start the peer discovery service
+    pub async fn start(&self) -> Result<(), PeerDiscoveryError> {
+        let mut is_running = self.is_running.write().await;
+        if *is_running {
+            return Ok(());
+        }
+        *is_running = true;
+        drop(is_running);
+
+        info!("Starting peer discovery service");
+        
+        // Still within the `restart_grace` of the last stop(), pick up where it left off
+        let pending_teardown = self.pending_teardown.lock().unwrap().take();
+        if let Some(teardown) = pending_teardown {
+            teardown.abort();
+            info!("Resuming the registration kept since the last stop");
+            if let Err(e) = self.resume_services().await {
+                return Err(self.abort_start(e).await);
+            }
+            return Ok(());
+        }
+        
+        self.claim_service_name().await;
+        
+        if let Err(e) = self.ensure_transport() {
+            return Err(self.abort_start(e).await);
+        }
+        
+        // Nothing is running unless every step succeeded, so a later start() tries again
+        let port = match self.claim_port() {
+            Ok(port) => port,
+            Err(e) => return Err(self.abort_start(e).await),
+        };
+        *self.port.write().await = port;
+        
+        // A missing or unreadable cache only costs us the head start
+        if let Err(e) = self.load_peer_cache().await {
+            warn!("Failed to load the peer cache: {}", e);
+        }
+        
+        if !self.multicast_available() {
+            self.start_manual_only().await;
+        } else if let Err(e) = self.start_services().await {
+            if self.config.port_range.is_some() {
+                claimed_ports().lock().unwrap().remove(&port);
+            }
+            return Err(self.abort_start(e).await);
+        }
+        
+        self.peer_sender.send(PeerEvent::ServiceStarted);
+        info!("Peer discovery service started successfully");
+        
+        Ok(())
+    }
+
+    /// Undo a partial `start()` and report why it failed
+    async fn abort_start(&self, e: PeerDiscoveryError) -> PeerDiscoveryError {
+        error!("Failed to start peer discovery service: {}", e);
+        self.abort_tasks();
+        // Registration may have succeeded before a later step failed
+        if let Some(fullname) = self.registered_fullname.write().await.take() {
+            if let Ok(transport) = self.transport() {
+                let _ = transport.unregister(&fullname);
+            }
+        }
+        claimed_service_names().lock().unwrap().remove(&self.local_fullname());
+        *self.is_running.write().await = false;
+        self.peer_sender.send(PeerEvent::Error(e.clone()));
+        e
+    }
+
+    /// Whether the transport can reach any peers, for mDNS whether any interface can multicast
+    fn multicast_available(&self) -> bool {
+        let transport = self.transport.lock().unwrap().clone();
+        transport.is_none_or(|transport| transport.multicast_available())
+    }
+
+    /// Run without registering or browsing, so only manual and cached peers show up
+    async fn start_manual_only(&self) {
+        *self.manual_only.write().await = true;
+        
+        let e = PeerDiscoveryError::NetworkInterfaceError(
+            "multicast unavailable on every interface, only manually added peers will be found".to_string()
+        );
+        warn!("{}", e);
+        self.peer_sender.send(PeerEvent::Error(e));
+        
+        self.start_pruning();
+        self.start_cache_saving();
+    }
+
+    /// Everything `start()` brings up once `is_running` is set and the port is claimed
+    async fn start_services(&self) -> Result<(), PeerDiscoveryError> {
+        // Register our own service
+        self.register_service().await?;
+        
+        self.start_background_services().await
+    }
+
+    /// Bring the background tasks back after a stop() within `restart_grace`
+    async fn resume_services(&self) -> Result<(), PeerDiscoveryError> {
+        if self.is_manual_only().await {
+            self.start_pruning();
+            self.start_cache_saving();
+            return Ok(());
+        }
+        
+        self.start_background_services().await
+    }
+
+    /// Browsing and the periodic tasks, everything `start_services` runs besides registering
+    async fn start_background_services(&self) -> Result<(), PeerDiscoveryError> {
+        // Start discovery
+        self.start_discovery().await?;
+
+        // Keep our service fresh for peers that missed the first announcement
+        self.start_announcing();
+
+        // Take the name the daemon picks when another device already uses ours
+        self.start_conflict_watch();
+
+        // Drop peers whose removal we never heard about
+        self.start_pruning();
+
+        // Follow network changes so we don't keep advertising a stale address
+        self.start_network_watch();
+
+        // Save battery once nothing new shows up
+        self.start_idle_watch();
+
+        // Keep the cache recent in case we're killed without a stop()
+        self.start_cache_saving();
+        
+        Ok(())
+    }
+
+    /// Stop the peer discovery service
+    ///
+    /// With `restart_grace` set only the background tasks stop right away, see `DiscoveryConfig::restart_grace`.
+    pub async fn stop(&self) -> Result<(), PeerDiscoveryError> {
+        self.stop_after(self.config.restart_grace).await
+    }
+
+    /// Stop, tearing down the registration after `grace` unless `start()` is called first
+    async fn stop_after(&self, grace: Option<Duration>) -> Result<(), PeerDiscoveryError> {
+        let mut is_running = self.is_running.write().await;
+        if !*is_running {
+            return Ok(());
+        }
+        *is_running = false;
+        drop(is_running);
+
+        info!("Stopping peer discovery service");
+        
+        if let Some(grace) = grace {
+            self.abort_tasks();
+            
+            // Hold the slot while spawning so even a zero grace finds its handle there
+            let mut pending_teardown = self.pending_teardown.lock().unwrap();
+            let discovery = self.task_handle();
+            let task = tokio::spawn(async move {
+                sleep(grace).await;
+                // Whoever takes the handle wins, start() or us
+                let claimed = discovery.pending_teardown.lock().unwrap().take().is_some();
+                if claimed {
+                    discovery.teardown().await;
+                }
+            });
+            *pending_teardown = Some(task.abort_handle());
+            return Ok(());
+        }
+        
+        self.teardown().await;
+        Ok(())
+    }
+
+    /// Unregister, shut the transport down and forget the peers, ending with `ServiceStopped`
+    async fn teardown(&self) {
+        let transport = self.transport.lock().unwrap().take();
+        if let Some(transport) = transport {
+            // Unregister our service under the name it was actually registered with
+            if let Some(fullname) = self.registered_fullname.write().await.take() {
+                if let Err(e) = transport.unregister(&fullname) {
+                    warn!("Failed to unregister service {}: {}", fullname, e);
+                }
+            }
+            
+            // Stop every browse task
+            for service_type in self.browse_types.read().await.iter() {
+                if let Err(e) = transport.stop_browse(service_type) {
+                    warn!("Failed to stop browsing {}: {}", service_type, e);
+                }
+            }
+            
+            // Don't leave the daemon's threads idling until the next start()
+            if let Err(e) = transport.shutdown().await {
+                warn!("Failed to shut down the mDNS daemon: {}", e);
+            }
+        }
+        
+        *self.registered_ip.write().await = None;
+        *self.manual_only.write().await = false;
+        
+        // Hand our name and port back to other instances
+        claimed_service_names().lock().unwrap().remove(&self.local_fullname());
+        if self.config.port_range.is_some() {
+            let port = self.port().await;
+            claimed_ports().lock().unwrap().remove(&port);
+        }
+        
+        // Make sure no background task outlives this run
+        self.abort_tasks();
+        
+        if let Err(e) = self.save_peer_cache().await {
+            warn!("Failed to save the peer cache: {}", e);
+        }
+        
+        // Clear discovered peers
+        {
+            let mut peers = self.discovered_peers.write().await;
+            peers.clear();
+        }
+        self.update_windows.lock().unwrap().clear();
+        
+        self.peer_sender.send(PeerEvent::ServiceStopped);
+        info!("Peer discovery service stopped");
+    }
+
+    /// Restart the service with a freshly detected address, keeping existing subscribers
+    ///
+    /// Emits `ServiceStopped` followed by `ServiceStarted`.
+    pub async fn restart(&self) -> Result<(), PeerDiscoveryError> {
+        info!("Restarting peer discovery service");
+        
+        // A resumed registration would keep the old address, so skip `restart_grace`
+        self.stop_after(None).await?;
+        self.start().await
+    }
+
+    /// Whether the discovery service is currently running
+    pub async fn is_running(&self) -> bool {
+        *self.is_running.read().await
+    }
+
+    /// Whether `start()` found no interface able to multicast and fell back to manual peers only
+    ///
+    /// Nothing is registered or browsed in this mode; peers come from `add_manual_peer` and the cache.
+    pub async fn is_manual_only(&self) -> bool {
+        *self.manual_only.read().await
+    }
+
+    /// Describe our own registration as other peers will see it, once the service is live
+    pub async fn local_peer(&self) -> Option<Peer> {
+        if !self.is_running().await {
+            return None;
+        }
+        let ip = (*self.registered_ip.read().await)?;
+        let properties = self.advertised_properties().await;
+        let raw_properties = properties.iter()
+            .map(|(key, value)| (key.clone(), value.as_bytes().to_vec()))
+            .collect();
+        
+        Some(Peer {
+            id: self.config.instance_id.clone(),
+            name: self.local_fullname(),
+            hostname: self.local_hostname(),
+            ip,
+            addresses: vec![ip],
+            address_pending: false,
+            port: self.port().await,
+            service_type: self.config.service_type.clone(),
+            properties,
+            raw_properties,
+            compatible: true,
+            source: PeerSource::Mdns,
+            last_seen: SystemTime::now(),
+        })
+    }
+
+    /// Name we register under, `config.service_name` with a suffix if that name was taken
+    pub fn service_name(&self) -> String {
+        self.service_name.lock().unwrap().clone()
+    }
+
+    /// Pick a service name no other instance here or known peer is using
+    ///
+    /// Devices we haven't heard from yet are caught by the daemon's probing, see `start_conflict_watch`.
+    async fn claim_service_name(&self) {
+        let taken_by_peers: HashSet<String> = self.discovered_peers.read().await
+            .values()
+            .filter(|peer| peer.id != self.config.instance_id)
+            .map(|peer| peer.name.clone())
+            .collect();
+        let fullname = |name: &str| format!("{}.{}", name, self.config.service_type);
+        
+        let mut claimed = claimed_service_names().lock().unwrap();
+        let mut name = self.config.service_name.clone();
+        while claimed.contains(&fullname(&name)) || taken_by_peers.contains(&fullname(&name)) {
+            let suffix = random_u64() & 0xffff;
+            name = format!("{}-{:04x}", self.config.service_name, suffix);
+        }
+        
+        if name != self.config.service_name {
+            info!("Service name {} is taken, using {} instead", self.config.service_name, name);
+        }
+        claimed.insert(fullname(&name));
+        *self.service_name.lock().unwrap() = name;
+    }
+
+    /// Fullname of our own registration as it appears in mDNS
+    fn local_fullname(&self) -> String {
+        format!("{}.{}", self.service_name(), self.config.service_type)
+    }
+
+    /// Hostname we advertise in our SRV record
+    fn local_hostname(&self) -> String {
+        // A name the daemon renamed to "name (2)" becomes "name-2", like the daemon's own host renames
+        format!("{}.local.", self.service_name().replace(' ', "-").replace(['(', ')'], ""))
+    }
+
+    /// Whether a resolved peer is our own service
+    fn is_self(&self, peer: &Peer) -> bool {
+        peer.id == self.config.instance_id || normalize_fullname(&peer.name) == normalize_fullname(&self.local_fullname())
+    }
+
+    /// Port the service is advertised on, which may differ from `config.port` when a
+    /// `port_range` fallback was used
+    pub async fn port(&self) -> u16 {
+        *self.port.read().await
+    }
+
+    /// Pick the configured port, or the first free one in `port_range` if it is taken
+    fn claim_port(&self) -> Result<u16, PeerDiscoveryError> {
+        let Some(port_range) = self.config.port_range.clone() else {
+            return Ok(self.config.port);
+        };
+        
+        let mut claimed = claimed_ports().lock().unwrap();
+        for port in std::iter::once(self.config.port).chain(port_range.clone()) {
+            if claimed.contains(&port) || std::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_err() {
+                continue;
+            }
+            
+            if port != self.config.port {
+                info!("Port {} is taken, using {} instead", self.config.port, port);
+            }
+            claimed.insert(port);
+            return Ok(port);
+        }
+        
+        Err(PeerDiscoveryError::ServiceRegistrationFailed(format!(
+            "no free port in {}..={}",
+            port_range.start(),
+            port_range.end()
+        )))
+    }
+
+    /// Track a background task so it is aborted on `stop()` or when the last handle is dropped
+    fn track(&self, task: AbortHandle) {
+        self.tasks.lock().unwrap().push(task);
+    }
+
+    /// The current transport, failing while `stop()` has it shut down
+    fn transport(&self) -> Result<Arc<dyn DiscoveryBackend>, PeerDiscoveryError> {
+        self.transport.lock().unwrap()
+            .clone()
+            .ok_or_else(|| PeerDiscoveryError::MdnsError("mDNS daemon is shut down".to_string()))
+    }
+
+    /// Replace the transport `stop()` shut down
+    fn ensure_transport(&self) -> Result<(), PeerDiscoveryError> {
+        let mut transport = self.transport.lock().unwrap();
+        if transport.is_none() {
+            *transport = Some(new_backend(&self.config)?);
+        }
+        Ok(())
+    }
+
+    fn abort_tasks(&self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Copy of this instance for a background task, which must not keep the tasks alive on drop
+    fn task_handle(&self) -> Self {
+        Self {
+            shutdown: None,
+            ..self.clone()
+        }
+    }
+
+    /// Get a receiver for peer events
+    ///
+    /// The channel holds `event_buffer_size` events; a receiver that falls further behind
+    /// gets `RecvError::Lagged` and misses the oldest events. Use `subscribe_unbounded`
+    /// when every event matters.
+    pub fn subscribe(&self) -> broadcast::Receiver<PeerEvent> {
+        self.peer_sender.subscribe()
+    }
+
+    /// Get a lossless receiver for peer events
+    ///
+    /// Events queue up without limit until received, so a stalled consumer grows memory
+    /// instead of dropping events.
+    pub fn subscribe_unbounded(&self) -> mpsc::UnboundedReceiver<PeerEvent> {
+        self.peer_sender.subscribe_unbounded()
+    }
+
+    /// Get peer events as a `Stream`, skipping any events lost to receiver lag
+    pub fn event_stream(&self) -> impl Stream<Item = PeerEvent> {
+        BroadcastStream::new(self.peer_sender.subscribe())
+            .filter_map(|event| event.ok())
+    }
+
+    /// Collect every event emitted during the next `within`, for tests and diagnostics
+    pub async fn drain_events(&self, within: Duration) -> Vec<PeerEvent> {
+        let mut receiver = self.subscribe();
+        let mut events = Vec::new();
+        
+        let _ = tokio::time::timeout(within, async {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => events.push(event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }).await;
+        
+        events
+    }
+
+    /// Get only peers being added or removed, without lifecycle, update or error events
+    pub fn subscribe_peer_changes(&self) -> impl Stream<Item = PeerChange> {
+        self.event_stream().filter_map(|event| match event {
+            PeerEvent::PeerDiscovered(peer) => Some(PeerChange::Added(peer)),
+            PeerEvent::PeerLost(peer) => Some(PeerChange::Removed(peer)),
+            _ => None,
+        })
+    }
+
+    /// The last `max` events, oldest first, whether or not anyone was subscribed when they happened
+    pub fn recent_events(&self, max: usize) -> Vec<(Instant, PeerEvent)> {
+        self.peer_sender.recent(max)
+    }
+
+    /// Get counters for the events handled so far
+    pub async fn stats(&self) -> DiscoveryStats {
+        let counters = &self.peer_sender.counters;
+        DiscoveryStats {
+            discovered: counters.discovered.load(Ordering::Relaxed),
+            pending: counters.pending.load(Ordering::Relaxed),
+            updated: counters.updated.load(Ordering::Relaxed),
+            lost: counters.lost.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+            current_count: self.discovered_peers.read().await.len(),
+        }
+    }
+
+    /// Get all currently discovered peers
+    pub async fn get_peers(&self) -> Vec<Peer> {
+        let peers = self.discovered_peers.read().await;
+        peers.values().cloned().collect()
+    }
+
+    /// Run `f` over the discovered peers, keyed by ID, without cloning them
+    ///
+    /// The read lock is held while `f` runs, which blocks peer updates, so keep `f` short
+    /// and don't call back into this instance from it.
+    pub async fn with_peers<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&HashMap<String, Peer>) -> R,
+    {
+        let peers = self.discovered_peers.read().await;
+        f(&peers)
+    }
+
+    /// Get all currently discovered peers in a stable order
+    pub async fn get_peers_sorted(&self, sort: PeerSort) -> Vec<Peer> {
+        let mut peers = self.get_peers().await;
+        peers.sort_by(|a, b| sort.compare(a, b));
+        peers
+    }
+
+    /// Get one page of discovered peers, ordered by name (then id) so pages are stable
+    pub async fn get_peers_paged(&self, offset: usize, limit: usize) -> Vec<Peer> {
+        let peers = self.discovered_peers.read().await;
+        let mut page: Vec<&Peer> = peers.values().collect();
+        page.sort_by(|a, b| PeerSort::ByName.compare(a, b));
+        
+        page.into_iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of currently discovered peers
+    pub async fn peer_count(&self) -> usize {
+        self.discovered_peers.read().await.len()
+    }
+
+    /// Forget every discovered peer while discovery keeps running, optionally emitting `PeerLost` for each
+    pub async fn clear_peers(&self, emit_lost: bool) {
+        let removed: Vec<Peer> = self.discovered_peers.write().await
+            .drain()
+            .map(|(_, peer)| peer)
+            .collect();
+        self.update_windows.lock().unwrap().clear();
+        
+        if emit_lost {
+            for peer in removed {
+                self.peer_sender.send(PeerEvent::PeerLost(peer));
+            }
+        }
+    }
+
+    /// Add a peer known out-of-band, for networks where mDNS is blocked
+    ///
+    /// The peer is marked `PeerSource::Manual` and kept until `remove_manual_peer`, pruning skips it.
+    /// Adding an existing ID replaces that peer and emits `PeerUpdated` instead of `PeerDiscovered`.
+    pub async fn add_manual_peer(&self, mut peer: Peer) {
+        peer.source = PeerSource::Manual;
+        peer.last_seen = SystemTime::now();
+        
+        let previous = self.discovered_peers.write().await.insert(peer.id.clone(), peer.clone());
+        let event = match previous {
+            None => PeerEvent::PeerDiscovered(peer),
+            Some(_) => PeerEvent::PeerUpdated(peer),
+        };
+        self.peer_sender.send(event);
+    }
+
+    /// Remove a manually added peer by name, emitting `PeerLost`; peers found via mDNS are left alone
+    pub async fn remove_manual_peer(&self, name: &str) -> Option<Peer> {
+        let removed = {
+            let mut peers = self.discovered_peers.write().await;
+            let id = peers.values()
+                .find(|peer| peer.name == name && peer.source == PeerSource::Manual)
+                .map(|peer| peer.id.clone())?;
+            peers.remove(&id)
+        };
+        
+        if let Some(peer) = &removed {
+            self.emit_lost(peer.clone());
+        }
+        removed
+    }
+
+    /// Feed an mDNS event to the running browse as if the network had delivered it.
+    /// Only backends that accept injected events support this, such as the in-memory one of
+    /// `DiscoveryConfig::test_mode` where nothing else produces events.
+    pub fn inject_service_event(&self, event: ServiceEvent) -> Result<(), PeerDiscoveryError> {
+        if self.transport()?.inject(event) {
+            Ok(())
+        } else {
+            Err(PeerDiscoveryError::ServiceDiscoveryFailed(
+                "No browse accepted the injected event".to_string()
+            ))
+        }
+    }
+
+    /// Get all currently discovered peers as a pretty-printed JSON array
+    pub async fn peers_as_json(&self) -> Result<String, PeerDiscoveryError> {
+        let peers = self.get_peers().await;
+        Ok(serde_json::to_string_pretty(&peers)?)
+    }
+
+    /// Get a specific peer by name
+    pub async fn get_peer(&self, name: &str) -> Option<Peer> {
+        let peers = self.discovered_peers.read().await;
+        peers.values().find(|peer| peer.name == name).cloned()
+    }
+
+    /// Get a specific peer by its stable ID
+    pub async fn get_peer_by_id(&self, id: &str) -> Option<Peer> {
+        let peers = self.discovered_peers.read().await;
+        peers.get(id).cloned()
+    }
+
+    /// Get a peer advertising the given address
+    pub async fn get_peer_by_ip(&self, ip: IpAddr) -> Option<Peer> {
+        let peers = self.discovered_peers.read().await;
+        peers.values()
+            .find(|peer| peer.ip == ip || peer.addresses.contains(&ip))
+            .cloned()
+    }
+
+    /// Get all peers whose `device_type` property matches
+    pub async fn get_peers_by_device_type(&self, device_type: &str) -> Vec<Peer> {
+        let peers = self.discovered_peers.read().await;
+        peers.values()
+            .filter(|peer| peer.properties.get(DEVICE_TYPE_PROPERTY).map(String::as_str) == Some(device_type))
+            .cloned()
+            .collect()
+    }
+
+    /// Current peers bucketed by their `device_type` property, "unknown" when they don't advertise one
+    ///
+    /// Each bucket is sorted by name.
+    pub async fn peers_by_device_type(&self) -> HashMap<String, Vec<Peer>> {
+        let mut groups: HashMap<String, Vec<Peer>> = HashMap::new();
+        for peer in self.get_peers_sorted(PeerSort::ByName).await {
+            let device_type = peer.properties.get(DEVICE_TYPE_PROPERTY)
+                .cloned()
+                .unwrap_or_else(|| "unknown".to_string());
+            groups.entry(device_type).or_default().push(peer);
+        }
+        groups
+    }
+
+    /// Search peers by name or device type, case-insensitively and by substring
+    pub async fn find_peers(&self, query: &str) -> Vec<Peer> {
+        let query = query.trim();
+        let peers = self.discovered_peers.read().await;
+        peers.values()
+            .filter(|peer| {
+                let name = peer.instance_name();
+                let device_type = peer.properties.get(DEVICE_TYPE_PROPERTY).map(String::as_str).unwrap_or("");
+                
+                contains_ignore_case(name, query) || contains_ignore_case(device_type, query)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// List every service type advertised on the network within `timeout_duration`, sorted
+    ///
+    /// Meant for diagnostics: an empty list usually means multicast isn't getting through.
+    pub async fn browse_all_service_types(&self, timeout_duration: Duration) -> Result<Vec<String>, PeerDiscoveryError> {
+        let transport = self.transport()?;
+        let mut receiver = transport.browse(META_SERVICE_TYPE)?;
+        
+        let mut service_types = BTreeSet::new();
+        let _ = tokio::time::timeout(timeout_duration, async {
+            while let Some(event) = receiver.recv().await {
+                // Meta-query answers name a service type where an instance name would be
+                if let ServiceEvent::ServiceFound(_, service_type) = event {
+                    service_types.insert(service_type);
+                }
+            }
+        }).await;
+        
+        if let Err(e) = transport.stop_browse(META_SERVICE_TYPE) {
+            warn!("Failed to stop browsing {}: {}", META_SERVICE_TYPE, e);
+        }
+        Ok(service_types.into_iter().collect())
+    }
+
+    /// Discover peers with a timeout
+    pub async fn discover_peers(&self, timeout_duration: Option<Duration>) -> Result<Vec<Peer>, PeerDiscoveryError> {
+        Ok(self.discover_peers_detailed(timeout_duration).await?.peers)
+    }
+
+    /// Discover peers like `discover_peers`, also reporting how the scan went
+    pub async fn discover_peers_detailed(&self, timeout_duration: Option<Duration>) -> Result<DiscoverResult, PeerDiscoveryError> {
+        let timeout_duration = self.discovery_timeout(timeout_duration)?;
+        
+        info!("Starting peer discovery with timeout: {:?}", timeout_duration);
+        
+        let started = Instant::now();
+        // Subscribe before starting so every event of the scan is counted
+        let mut receiver = self.subscribe();
+        
+        // Start discovery if not already running
+        if !*self.is_running.read().await {
+            self.start().await?;
+        }
+        
+        let mut progress = self.config.scan_progress_interval
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| tokio::time::interval_at(tokio::time::Instant::now() + interval, interval));
+        
+        let mut events_seen = 0;
+        let timed_out = tokio::time::timeout(timeout_duration, async {
+            loop {
+                // Return as soon as enough peers are known
+                if let Some(min_peers) = self.config.early_exit_after {
+                    if self.discovered_peers.read().await.len() >= min_peers {
+                        break;
+                    }
+                }
+                
+                tokio::select! {
+                    event = receiver.recv() => match event {
+                        // Progress reports are about scans, not something the scan saw
+                        Ok(PeerEvent::ScanProgress { .. }) => {}
+                        Ok(_) => events_seen += 1,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => events_seen += skipped as usize,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = next_tick(progress.as_mut()) => {
+                        let peers_found = self.discovered_peers.read().await.len();
+                        self.peer_sender.send(PeerEvent::ScanProgress {
+                            elapsed: started.elapsed(),
+                            peers_found,
+                        });
+                    }
+                }
+            }
+        }).await.is_err();
+        
+        let peers = self.get_peers().await;
+        info!("Discovered {} peers", peers.len());
+        
+        Ok(DiscoverResult {
+            peers,
+            elapsed: started.elapsed(),
+            timed_out,
+            events_seen,
+        })
+    }
+
+    /// Discover peers, failing unless at least `min_peers` are found before the timeout
+    ///
+    /// Returns as soon as `min_peers` are known instead of waiting out the full timeout.
+    pub async fn discover_peers_strict(&self, min_peers: usize, timeout_duration: Option<Duration>) -> Result<Vec<Peer>, PeerDiscoveryError> {
+        let timeout_duration = self.discovery_timeout(timeout_duration)?;
+        
+        info!("Starting strict peer discovery for {} peers with timeout: {:?}", min_peers, timeout_duration);
+        
+        let peers = self.wait_for_peers(min_peers, timeout_duration).await?;
+        if peers.len() < min_peers {
+            return Err(PeerDiscoveryError::DiscoveryTimeout(format!(
+                "found {} of {} required peers within {:?}",
+                peers.len(), min_peers, timeout_duration
+            )));
+        }
+        
+        info!("Discovered {} peers", peers.len());
+        
+        Ok(peers)
+    }
+
+    /// The requested timeout or the configured default, rejecting zero and anything over the maximum
+    fn discovery_timeout(&self, timeout_duration: Option<Duration>) -> Result<Duration, PeerDiscoveryError> {
+        let timeout_duration = timeout_duration.unwrap_or(self.config.discovery_timeout);
+        
+        if timeout_duration.is_zero() {
+            return Err(PeerDiscoveryError::DiscoveryTimeout(
+                "timeout must be greater than zero".to_string()
+            ));
+        }
+        if timeout_duration > self.config.max_discovery_timeout {
+            return Err(PeerDiscoveryError::DiscoveryTimeout(format!(
+                "timeout {:?} exceeds the maximum of {:?}",
+                timeout_duration, self.config.max_discovery_timeout
+            )));
+        }
+        
+        Ok(timeout_duration)
+    }
+
+    /// Wait until at least `min_peers` are known or the timeout elapses
+    async fn wait_for_peers(&self, min_peers: usize, timeout_duration: Duration) -> Result<Vec<Peer>, PeerDiscoveryError> {
+        // Subscribe before starting so no discovery event is missed
+        let mut receiver = self.subscribe();
+        
+        if !*self.is_running.read().await {
+            self.start().await?;
+        }
+        
+        let _ = tokio::time::timeout(timeout_duration, async {
+            while self.discovered_peers.read().await.len() < min_peers {
+                if let Err(broadcast::error::RecvError::Closed) = receiver.recv().await {
+                    break;
+                }
+            }
+        }).await;
+        
+        Ok(self.get_peers().await)
+    }
+
+    /// Wait for one specific service to resolve, returning as soon as it does
+    ///
+    /// Starts the service if it isn't running yet, like `discover_peers`.
+    pub async fn resolve(&self, service_name: &str, timeout_duration: Duration) -> Result<Peer, PeerDiscoveryError> {
+        let fullname = normalize_fullname(&format!("{}.{}", service_name, self.config.service_type));
+        
+        if !*self.is_running.read().await {
+            self.start().await?;
+        }
+        
+        self.wait_for_peer(|peer| normalize_fullname(&peer.name) == fullname, timeout_duration).await
+            .map_err(|_| PeerDiscoveryError::DiscoveryTimeout(format!(
+                "{} did not resolve within {:?}",
+                service_name, timeout_duration
+            )))
+    }
+
+    /// Wait until a peer matching `predicate` is known, checking the current peers first
+    pub async fn wait_for_peer<F: Fn(&Peer) -> bool>(&self, predicate: F, timeout_duration: Duration) -> Result<Peer, PeerDiscoveryError> {
+        // Subscribe before looking so a peer arriving in between isn't missed
+        let mut receiver = self.subscribe();
+        
+        let found = tokio::time::timeout(timeout_duration, async {
+            loop {
+                if let Some(peer) = self.discovered_peers.read().await.values().find(|peer| predicate(peer)) {
+                    return Some(peer.clone());
+                }
+                if let Err(broadcast::error::RecvError::Closed) = receiver.recv().await {
+                    return None;
+                }
+            }
+        }).await;
+        
+        found.ok().flatten().ok_or_else(|| PeerDiscoveryError::DiscoveryTimeout(format!(
+            "no matching peer within {:?}",
+            timeout_duration
+        )))
+    }
+
+    /// Wait until `start()` has finished bringing the service up, for callers that may have missed `ServiceStarted`
+    pub async fn wait_until_ready(&self, timeout_duration: Duration) -> Result<(), PeerDiscoveryError> {
+        // Subscribe before looking so a start finishing in between isn't missed
+        let mut receiver = self.subscribe();
+        
+        let ready = tokio::time::timeout(timeout_duration, async {
+            loop {
+                if self.is_ready().await {
+                    return true;
+                }
+                if let Err(broadcast::error::RecvError::Closed) = receiver.recv().await {
+                    return false;
+                }
+            }
+        }).await;
+        
+        match ready {
+            Ok(true) => Ok(()),
+            _ => Err(PeerDiscoveryError::DiscoveryTimeout(format!(
+                "service not ready within {:?}",
+                timeout_duration
+            ))),
+        }
+    }
+
+    /// Running and past registration, `is_running` alone is set as soon as `start()` begins
+    async fn is_ready(&self) -> bool {
+        self.is_running().await
+            && (self.registered_fullname.read().await.is_some() || self.is_manual_only().await)
+    }
+
+    /// Query the network again and return the peer's details once it re-resolves
+    ///
+    /// Falls back to what we already know when no fresh resolution arrives within the timeout,
+    /// and returns `None` for unknown peers or ones lost in the meantime.
+    pub async fn refresh_peer(&self, id: &str, timeout_duration: Duration) -> Option<Peer> {
+        self.get_peer_by_id(id).await?;
+        
+        let requested = SystemTime::now();
+        self.requery.notify_waiters();
+        
+        match self.wait_for_peer(|peer| peer.id == id && peer.last_seen >= requested, timeout_duration).await {
+            Ok(peer) => Some(peer),
+            Err(_) => self.get_peer_by_id(id).await,
+        }
+    }
+
+    /// Open a TCP connection to a peer, trying each of its addresses in order
+    ///
+    /// Waits for a free slot when `max_concurrent_connections` attempts are already in flight.
+    pub async fn connect_to(&self, peer: &Peer) -> Result<TcpStream, PeerDiscoveryError> {
+        // The semaphore is never closed, so this only fails if that changes
+        let _permit = self.connection_permits.acquire().await
+            .map_err(|e| PeerDiscoveryError::ConnectionFailed(e.to_string()))?;
+        
+        let mut failures = Vec::new();
+        for addr in peer.socket_addrs() {
+            match tokio::time::timeout(self.config.connect_timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => {
+                    debug!("Connected to {} at {}", peer.name, addr);
+                    return Ok(stream);
+                }
+                Ok(Err(e)) => failures.push(format!("{}: {}", addr, e)),
+                Err(_) => failures.push(format!("{}: timed out", addr)),
+            }
+        }
+        
+        Err(PeerDiscoveryError::ConnectionFailed(format!(
+            "could not reach {} ({})",
+            peer.name,
+            failures.join(", ")
+        )))
+    }
+
+    /// Check that a peer actually accepts connections at one of its advertised addresses
+    pub async fn verify_peer(&self, peer: &Peer) -> bool {
+        self.connect_to(peer).await.is_ok()
+    }
+
+    /// Get the discovered peers that are currently reachable, checked concurrently
+    pub async fn get_reachable_peers(&self) -> Vec<Peer> {
+        let mut checks = JoinSet::new();
+        for peer in self.get_peers().await {
+            let discovery = self.task_handle();
+            checks.spawn(async move {
+                let reachable = discovery.verify_peer(&peer).await;
+                (peer, reachable)
+            });
+        }
+        
+        let mut reachable = Vec::new();
+        while let Some(result) = checks.join_next().await {
+            if let Ok((peer, true)) = result {
+                reachable.push(peer);
+            }
+        }
+        
+        reachable
+    }
+
+    /// Accept connections on our advertised port so `verify_peer` on other devices succeeds
+    ///
+    /// Each connection gets `LIVENESS_RESPONSE` and is closed. The listener runs until the
+    /// returned handle is aborted or `stop()` is called.
+    pub async fn serve_liveness(&self) -> Result<JoinHandle<()>, PeerDiscoveryError> {
+        let ip = self.config.bind_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let listener = TcpListener::bind(SocketAddr::new(ip, self.port().await)).await?;
+        info!("Serving liveness probes on {}", listener.local_addr()?);
+        
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((mut stream, addr)) => {
+                        debug!("Liveness probe from {}", addr);
+                        // A prober that hangs up early is not our problem
+                        tokio::spawn(async move {
+                            let _ = stream.write_all(LIVENESS_RESPONSE).await;
+                            let _ = stream.shutdown().await;
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept liveness probe: {}", e),
+                }
+            }
+        });
+        self.track(task.abort_handle());
+        Ok(task)
+    }
+
+    /// Register our own service for other peers to discover
+    async fn register_service(&self) -> Result<(), PeerDiscoveryError> {
+        let service_info = self.service_info().await?;
+        // Remember the primary address, the other family may be registered alongside it
+        let ip = self.config.ip_preference.select(service_info.get_addresses());
+        let fullname = service_info.get_fullname().to_string();
+        
+        self.transport()?.register(service_info)?;
+        *self.registered_ip.write().await = ip;
+        *self.registered_fullname.write().await = Some(fullname);
+        info!("Registered service: {} on port {}", self.service_name(), self.port().await);
+        
+        Ok(())
+    }
+
+    /// Periodically check whether our address changed and re-register when it did
+    fn start_network_watch(&self) {
+        // A fixed bind address never changes
+        if !self.config.auto_reannounce_on_network_change || self.config.bind_ip.is_some() {
+            return;
+        }
+        let discovery = self.task_handle();
+
+        let task = tokio::spawn(async move {
+            loop {
+                sleep(NETWORK_CHECK_INTERVAL).await;
+
+                if !*discovery.is_running.read().await {
+                    break;
+                }
+
+                if let Err(e) = discovery.reannounce_if_address_changed().await {
+                    error!("Failed to re-register after network change: {}", e);
+                    discovery.peer_sender.send(PeerEvent::Error(e));
+                }
+            }
+
+            debug!("Network watch stopped for service: {}", discovery.service_name());
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Follow the renames the transport makes when our name turns out to be taken on the network
+    fn start_conflict_watch(&self) {
+        let renames = self.transport().and_then(|transport| transport.name_changes());
+        let mut renames = match renames {
+            Ok(Some(renames)) => renames,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to watch for service name conflicts: {}", e);
+                return;
+            }
+        };
+        let discovery = self.task_handle();
+
+        let task = tokio::spawn(async move {
+            while let Some((original, renamed)) = renames.recv().await {
+                discovery.adopt_renamed_service(&original, &renamed).await;
+            }
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Take over the fullname the transport gave our registration, so re-announces keep it
+    async fn adopt_renamed_service(&self, original: &str, renamed: &str) {
+        let mut registered = self.registered_fullname.write().await;
+        if registered.as_deref().map(normalize_fullname) != Some(normalize_fullname(original)) {
+            return;
+        }
+        let Some(name) = renamed.strip_suffix(&format!(".{}", self.config.service_type)) else {
+            return;
+        };
+
+        warn!("Service name {} is taken on the network, now advertised as {}", self.service_name(), name);
+        {
+            let mut claimed = claimed_service_names().lock().unwrap();
+            claimed.remove(&self.local_fullname());
+            claimed.insert(renamed.to_string());
+        }
+        *self.service_name.lock().unwrap() = name.to_string();
+        *registered = Some(renamed.to_string());
+    }
+
+    /// Re-register if the detected local address differs from the registered one
+    ///
+    /// Returns whether a re-registration happened.
+    async fn reannounce_if_address_changed(&self) -> Result<bool, PeerDiscoveryError> {
+        let current = match self.config.ip_address().await {
+            Ok(ip) => ip,
+            Err(e) => {
+                // Interfaces can briefly disappear while switching networks
+                debug!("No local address while checking for network changes: {}", e);
+                return Ok(false);
+            }
+        };
+
+        let previous = *self.registered_ip.read().await;
+        if previous == Some(current) {
+            return Ok(false);
+        }
+
+        info!("Local address changed from {:?} to {}", previous, current);
+        self.register_service().await?;
+
+        if let Some(previous) = previous {
+            self.peer_sender.send(PeerEvent::LocalAddressChanged { previous, current });
+        }
+
+        Ok(true)
+    }
+
+    /// Build the service record we advertise to other peers
+    async fn service_info(&self) -> Result<ServiceInfo, PeerDiscoveryError> {
+        let service_type = match self.config.subtypes.first() {
+            Some(subtype) => subtype_service_type(subtype, &self.config.service_type),
+            None => self.config.service_type.clone(),
+        };
+        
+        let service_info = ServiceInfo::new(
+            &service_type,
+            &self.service_name(),
+            &self.local_hostname(),
+            &self.config.ip_addresses().await?[..],
+            self.port().await,
+            self.advertised_properties().await,
+        )?;
+
+        Ok(service_info)
+    }
+
+    /// TXT records we advertise: the configured properties along with our stable ID
+    async fn advertised_properties(&self) -> HashMap<String, String> {
+        let mut properties: HashMap<String, String> = self.properties.read().await.clone();
+        properties.insert(INSTANCE_ID_PROPERTY.to_string(), self.config.instance_id.clone());
+        properties
+    }
+
+    /// Merge new values into our advertised properties, re-registering right away when running
+    pub async fn update_properties(&self, properties: HashMap<String, String>) -> Result<(), PeerDiscoveryError> {
+        let merged = {
+            let mut current = self.properties.write().await;
+            current.extend(properties);
+            current.clone()
+        };
+        
+        if *self.is_running.read().await {
+            self.register_service().await?;
+        }
+        
+        self.peer_sender.send(PeerEvent::LocalPropertiesChanged(merged));
+        Ok(())
+    }
+
+    /// Re-register our service right away instead of waiting for the next periodic announcement
+    pub async fn announce(&self) -> Result<(), PeerDiscoveryError> {
+        if !*self.is_running.read().await {
+            return Err(PeerDiscoveryError::ServiceRegistrationFailed(
+                "discovery is not running".to_string()
+            ));
+        }
+        
+        debug!("Announcing service on request: {}", self.service_name());
+        self.register_service().await
+    }
+
+    /// Periodically re-register our service every `announce_interval` while running
+    fn start_announcing(&self) {
+        let discovery = self.task_handle();
+        let announce_interval = self.config.announce_interval;
+        let announce_jitter = self.config.announce_jitter;
+
+        let task = tokio::spawn(async move {
+            loop {
+                sleep(jittered_interval(announce_interval, announce_jitter).max(MIN_ANNOUNCE_INTERVAL)).await;
+
+                if !*discovery.is_running.read().await {
+                    break;
+                }
+
+                debug!("Re-announcing service: {}", discovery.service_name());
+                if let Err(e) = discovery.register_service().await {
+                    error!("Failed to re-announce service: {}", e);
+                    discovery.peer_sender.send(PeerEvent::Error(e));
+                }
+            }
+
+            debug!("Announce task stopped for service: {}", discovery.service_name());
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Periodically remove peers that have not been seen within `peer_ttl` while running
+    ///
+    /// mdns-sd only reports a peer again when its records change, so each sweep first queries
+    /// the network and only prunes the peers that stayed silent.
+    fn start_pruning(&self) {
+        let Some(peer_ttl) = self.config.peer_ttl else {
+            return;
+        };
+        let discovery = self.task_handle();
+        let sweep_interval = (peer_ttl / 2).max(Duration::from_secs(1));
+
+        let task = tokio::spawn(async move {
+            loop {
+                sleep(sweep_interval).await;
+
+                if !*discovery.is_running.read().await {
+                    break;
+                }
+
+                if discovery.stale_peer_ids(peer_ttl).await.is_empty() {
+                    continue;
+                }
+                // Give peers that are still online the chance to answer before they are pruned
+                discovery.requery.notify_waiters();
+                sleep(PRUNE_REQUERY_GRACE).await;
+
+                if !*discovery.is_running.read().await {
+                    break;
+                }
+
+                // Whoever answered was seen within the grace, whoever was stale before the query is older still
+                discovery.prune_stale_peers(peer_ttl + PRUNE_REQUERY_GRACE).await;
+            }
+
+            debug!("Pruning task stopped for service: {}", discovery.service_name());
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Save the peer list to `cache_path` every `CACHE_SAVE_INTERVAL`
+    fn start_cache_saving(&self) {
+        if self.config.cache_path.is_none() {
+            return;
+        }
+        let discovery = self.task_handle();
+
+        let task = tokio::spawn(async move {
+            loop {
+                sleep(CACHE_SAVE_INTERVAL).await;
+
+                if let Err(e) = discovery.save_peer_cache().await {
+                    warn!("Failed to save the peer cache: {}", e);
+                }
+            }
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Write the discovered peers to `cache_path`; manual peers are left to the app that added them
+    async fn save_peer_cache(&self) -> Result<(), PeerDiscoveryError> {
+        let Some(path) = &self.config.cache_path else {
+            return Ok(());
+        };
+
+        let peers: Vec<Peer> = self.discovered_peers.read().await
+            .values()
+            .filter(|peer| peer.source != PeerSource::Manual)
+            .cloned()
+            .collect();
+        let json = serde_json::to_vec(&peers)?;
+
+        // Write next to the cache and rename, so a crash never leaves half a file behind
+        let temp_path = path.with_extension("tmp");
+        tokio::fs::write(&temp_path, json).await?;
+        tokio::fs::rename(&temp_path, path).await?;
+        debug!("Saved {} peers to {}", peers.len(), path.display());
+        Ok(())
+    }
+
+    /// Add the peers saved in `cache_path` as `PeerSource::Cached`, returns how many were loaded
+    ///
+    /// Peers past `cache_max_age` and peers we already know are skipped.
+    async fn load_peer_cache(&self) -> Result<usize, PeerDiscoveryError> {
+        let Some(path) = &self.config.cache_path else {
+            return Ok(0);
+        };
+
+        let json = match tokio::fs::read(path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let cached: Vec<Peer> = serde_json::from_slice(&json)?;
+
+        let loaded: Vec<Peer> = {
+            let mut peers = self.discovered_peers.write().await;
+            cached.into_iter()
+                .filter(|peer| peer.last_seen.elapsed().unwrap_or_default() <= self.config.cache_max_age)
+                .filter_map(|mut peer| {
+                    if peers.contains_key(&peer.id) {
+                        return None;
+                    }
+                    peer.source = PeerSource::Cached;
+                    peers.insert(peer.id.clone(), peer.clone());
+                    Some(peer)
+                })
+                .collect()
+        };
+
+        debug!("Loaded {} cached peers from {}", loaded.len(), path.display());
+        for peer in &loaded {
+            self.peer_sender.send(PeerEvent::PeerDiscovered(peer.clone()));
+        }
+        Ok(loaded.len())
+    }
+
+    /// Stop the service once `idle_timeout` passes without a peer being discovered or updated
+    fn start_idle_watch(&self) {
+        let Some(idle_timeout) = self.config.idle_timeout else {
+            return;
+        };
+        let discovery = self.task_handle();
+        let mut receiver = self.subscribe();
+
+        let task = tokio::spawn(async move {
+            loop {
+                let activity = tokio::time::timeout(idle_timeout, async {
+                    loop {
+                        match recv_skipping_lag(&mut receiver).await {
+                            Some(PeerEvent::PeerDiscovered(_) | PeerEvent::PeerUpdated(_)) => return true,
+                            Some(_) => {}
+                            None => return false,
+                        }
+                    }
+                }).await;
+
+                match activity {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(_) => {
+                        info!("No peer activity for {:?}, stopping discovery", idle_timeout);
+                        // stop() aborts this task, so it has to run on its own
+                        tokio::spawn(async move {
+                            if let Err(e) = discovery.stop().await {
+                                warn!("Failed to stop idle discovery: {}", e);
+                            }
+                        });
+                        break;
+                    }
+                }
+            }
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// IDs of the peers not seen within `peer_ttl`
+    async fn stale_peer_ids(&self, peer_ttl: Duration) -> Vec<String> {
+        let peers = self.discovered_peers.read().await;
+        self.stale_ids(&peers, peer_ttl)
+    }
+
+    fn stale_ids(&self, peers: &HashMap<String, Peer>, peer_ttl: Duration) -> Vec<String> {
+        // Manual peers are never re-resolved, they stay until removed
+        peers.values()
+            .filter(|peer| {
+                let age = peer.last_seen.elapsed().unwrap_or_default();
+                match peer.source {
+                    PeerSource::Mdns => age > peer_ttl,
+                    PeerSource::Cached => age > self.config.cache_max_age,
+                    PeerSource::Manual => false,
+                }
+            })
+            .map(|peer| peer.id.clone())
+            .collect()
+    }
+
+    /// Remove peers not seen within `peer_ttl`, emitting `PeerLost` for each
+    async fn prune_stale_peers(&self, peer_ttl: Duration) -> Vec<Peer> {
+        let stale_peers: Vec<Peer> = {
+            let mut peers = self.discovered_peers.write().await;
+            let stale_ids = self.stale_ids(&peers, peer_ttl);
+
+            stale_ids.iter()
+                .filter_map(|id| peers.remove(id))
+                .collect()
+        };
+
+        for peer in &stale_peers {
+            debug!("Peer expired: {}", peer.name);
+            self.emit_lost(peer.clone());
+        }
+
+        stale_peers
+    }
+
+    /// Start discovering other peers
+    async fn start_discovery(&self) -> Result<(), PeerDiscoveryError> {
+        for service_type in self.browse_types.read().await.iter() {
+            self.browse(service_type.clone())?;
+        }
+        
+        Ok(())
+    }
+
+    /// Browse for an additional service type, feeding the same peer map and event channel
+    ///
+    /// Takes effect immediately when running, otherwise on the next `start()`.
+    pub async fn add_service_type(&self, service_type: String) -> Result<(), PeerDiscoveryError> {
+        validate_service_type(&service_type)?;
+        
+        {
+            let mut browse_types = self.browse_types.write().await;
+            if browse_types.contains(&service_type) {
+                return Ok(());
+            }
+            browse_types.push(service_type.clone());
+        }
+        
+        if *self.is_running.read().await {
+            self.browse(service_type)?;
+        }
+        
+        Ok(())
+    }
+
+    /// Spawn a task browsing for a single service type
+    ///
+    /// The first browse happens before spawning so its failure reaches the caller;
+    /// later failures are retried by the task and reported as `PeerEvent::Error`.
+    fn browse(&self, service_type: String) -> Result<(), PeerDiscoveryError> {
+        let initial = self.transport()?.browse(&service_type)?;
+        let discovery = self.task_handle();
+        
+        let task = tokio::spawn(async move {
+            let mut initial = Some(initial);
+            let mut backoff = BROWSE_RETRY_INITIAL;
+            
+            // Keep browsing until stopped, backing off while the daemon keeps failing
+            while *discovery.is_running.read().await {
+                let browse = match initial.take() {
+                    Some(receiver) => Ok(receiver),
+                    None => discovery.transport().and_then(|transport| transport.browse(&service_type)),
+                };
+                let failure = match browse {
+                    Ok(mut receiver) => {
+                        info!("Started browsing for service type: {}", service_type);
+                        backoff = BROWSE_RETRY_INITIAL;
+                        
+                        let mut requery = discovery.config.query_interval
+                            .filter(|interval| !interval.is_zero())
+                            .map(|interval| tokio::time::interval_at(tokio::time::Instant::now() + interval, interval));
+                        
+                        loop {
+                            tokio::select! {
+                                event = receiver.recv() => {
+                                    let Some(event) = event else { break };
+                                    if let Err(e) = discovery.handle_service_event(event).await {
+                                        error!("Error handling service event: {}", e);
+                                        discovery.peer_sender.send(PeerEvent::Error(e));
+                                    }
+                                }
+                                _ = discovery.requery_due(requery.as_mut()) => {
+                                    // Browsing the same type again replaces our receiver and makes the daemon query right away
+                                    match discovery.transport().and_then(|transport| transport.browse(&service_type)) {
+                                        Ok(fresh) => receiver = fresh,
+                                        Err(e) => warn!("Failed to re-query {}: {}", service_type, e),
+                                    }
+                                }
+                            }
+                        }
+                        
+                        if !*discovery.is_running.read().await {
+                            break;
+                        }
+                        format!("browsing for {} ended unexpectedly", service_type)
+                    }
+                    Err(e) => format!("failed to browse for {}: {}", service_type, e),
+                };
+                
+                warn!("{}, retrying in {:?}", failure, backoff);
+                discovery.peer_sender.send(PeerEvent::Error(PeerDiscoveryError::ServiceDiscoveryFailed(failure)));
+                
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(BROWSE_RETRY_MAX);
+            }
+        });
+        self.track(task.abort_handle());
+        Ok(())
+    }
+
+    /// Wait until a browse should query again, on the `query_interval` tick or when `refresh_peer` asks
+    async fn requery_due(&self, ticker: Option<&mut tokio::time::Interval>) {
+        tokio::select! {
+            _ = next_tick(ticker) => {}
+            _ = self.requery.notified() => {}
+        }
+    }
+
+    /// Send `PeerLost` for a peer just taken out of `discovered_peers`, closing its debounce window
+    fn emit_lost(&self, peer: Peer) {
+        self.update_windows.lock().unwrap().remove(&peer.id);
+        self.peer_sender.send(PeerEvent::PeerLost(peer));
+    }
+
+    /// Send `PeerUpdated`, holding it back while the peer's debounce window is open
+    fn emit_update(&self, peer: Peer) {
+        let Some(debounce) = self.config.update_debounce else {
+            self.peer_sender.send(PeerEvent::PeerUpdated(peer));
+            return;
+        };
+
+        let remaining = {
+            let mut windows = self.update_windows.lock().unwrap();
+            match windows.get_mut(&peer.id) {
+                Some(window) if window.emitted_at.elapsed() < debounce => {
+                    if window.pending {
+                        // The scheduled update will pick up this change
+                        return;
+                    }
+                    window.pending = true;
+                    debounce.saturating_sub(window.emitted_at.elapsed())
+                }
+                _ => {
+                    windows.insert(peer.id.clone(), UpdateWindow { emitted_at: Instant::now(), pending: false });
+                    drop(windows);
+                    self.peer_sender.send(PeerEvent::PeerUpdated(peer));
+                    return;
+                }
+            }
+        };
+
+        let discovery = self.task_handle();
+        let task = tokio::spawn(async move {
+            sleep(remaining).await;
+            // Report the latest state, unless the peer went away meanwhile
+            let Some(current) = discovery.get_peer_by_id(&peer.id).await else {
+                return;
+            };
+            if let Some(window) = discovery.update_windows.lock().unwrap().get_mut(&peer.id) {
+                window.emitted_at = Instant::now();
+                window.pending = false;
+            }
+            discovery.peer_sender.send(PeerEvent::PeerUpdated(current));
+        });
+        self.track(task.abort_handle());
+    }
+
+    /// Handle incoming service events (peer discovered/lost)
+    async fn handle_service_event(&self, event: ServiceEvent) -> Result<(), PeerDiscoveryError> {
+        let ip_preference = self.config.ip_preference;
+        
+        match event {
+            ServiceEvent::ServiceResolved(info) => {
+                // Keep a peer without a usable address so it shows up, a later resolve may bring one
+                let selected = ip_preference.select(info.get_addresses());
+                let address_pending = selected.is_none();
+                if address_pending {
+                    debug!("No address matching {:?} for {}, keeping it as pending", ip_preference, info.get_fullname());
+                }
+                let ip = selected.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+                // Keep every usable address, primary first and the rest in a stable order
+                let mut addresses: Vec<IpAddr> = info.get_addresses()
+                    .iter()
+                    .copied()
+                    .filter(|addr| ip_preference.allows(addr) && *addr != ip)
+                    .collect();
+                addresses.sort();
+                if !address_pending {
+                    addresses.insert(0, ip);
+                }
+
+                let raw_properties = raw_txt_properties(
+                    info.get_properties().iter().map(|prop| (prop.key(), prop.val()))
+                );
+                let properties = decode_txt_properties(&raw_properties);
+                
+                // Peers without a version can't prove they speak our protocol
+                let compatible = match &self.config.min_compatible_version {
+                    Some(minimum) => properties.get(VERSION_PROPERTY)
+                        .is_some_and(|version| version_at_least(version, minimum)),
+                    None => true,
+                };
+
+                let peer = Peer {
+                    // Fall back to the fullname for peers that don't advertise an instance ID
+                    id: properties.get(INSTANCE_ID_PROPERTY)
+                        .cloned()
+                        .unwrap_or_else(|| normalize_fullname(info.get_fullname())),
+                    name: info.get_fullname().to_string(),
+                    hostname: info.get_hostname().to_string(),
+                    ip,
+                    addresses,
+                    address_pending,
+                    port: info.get_port(),
+                    service_type: info.get_type().to_string(),
+                    properties,
+                    raw_properties,
+                    compatible,
+                    source: PeerSource::Mdns,
+                    last_seen: SystemTime::now(),
+                };
+                
+                if !self.config.include_self && self.is_self(&peer) {
+                    debug!("Ignoring our own service: {}", peer.name);
+                    return Ok(());
+                }
+                
+                let filtered_out = self.config.peer_filter.as_ref().is_some_and(|filter| !filter.admits(&peer));
+                if filtered_out || (!peer.compatible && self.config.drop_incompatible_peers) {
+                    debug!("Peer filtered out: {}", peer.name);
+                    // It may have been admitted before its advertisement changed
+                    let removed = self.discovered_peers.write().await.remove(&peer.id);
+                    if let Some(removed) = removed {
+                        self.emit_lost(removed);
+                    }
+                    return Ok(());
+                }
+                
+                // Add to discovered peers, remembering what we knew before
+                let (previous, evicted) = {
+                    let mut peers = self.discovered_peers.write().await;
+                    
+                    // Make room for a new peer by dropping the one we heard from least recently
+                    let mut evicted = None;
+                    let full = self.config.max_peers.is_some_and(|max_peers| peers.len() >= max_peers);
+                    if full && !peers.contains_key(&peer.id) {
+                        let oldest = peers.values()
+                            .filter(|known| known.source != PeerSource::Manual)
+                            .min_by_key(|known| known.last_seen)
+                            .map(|known| known.id.clone());
+                        let Some(oldest) = oldest else {
+                            debug!("Peer limit reached, ignoring {}", peer.name);
+                            return Ok(());
+                        };
+                        evicted = peers.remove(&oldest);
+                    }
+                    
+                    (peers.insert(peer.id.clone(), peer.clone()), evicted)
+                };
+                
+                if let Some(evicted) = evicted {
+                    debug!("Peer limit reached, evicted {}", evicted.name);
+                    self.emit_lost(evicted);
+                }
+                
+                match previous {
+                    None => {
+                        debug!("Peer discovered: {:?}", peer);
+                        self.peer_sender.send(PeerEvent::PeerDiscovered(peer));
+                    }
+                    Some(previous) if !previous.content_eq(&peer) => {
+                        debug!("Peer updated: {:?}", peer);
+                        self.emit_update(peer);
+                    }
+                    Some(_) => {
+                        debug!("Peer refreshed: {}", peer.name);
+                    }
+                }
+            }
+            ServiceEvent::ServiceRemoved(_, fullname) => {
+                debug!("Peer lost: {}", fullname);
+                
+                // Remove from discovered peers, which are keyed by ID rather than fullname
+                let fullname = normalize_fullname(&fullname);
+                let removed_peer = {
+                    let mut peers = self.discovered_peers.write().await;
+                    let id = peers.values()
+                        .find(|peer| normalize_fullname(&peer.name) == fullname)
+                        .map(|peer| peer.id.clone());
+                    id.and_then(|id| peers.remove(&id))
+                };
+                
+                if let Some(peer) = removed_peer {
+                    self.emit_lost(peer);
+                }
+            }
+            _ => {
+                debug!("Unhandled service event: {:?}", event);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+impl DiscoveryConfig {
+    /// Make sure every multicast interface address belongs to this machine
+    fn validate_multicast_interfaces(&self) -> Result<(), PeerDiscoveryError> {
+        if self.multicast_interfaces.is_empty() {
+            return Ok(());
+        }
+
+        let local: HashSet<IpAddr> = local_interfaces()?
+            .into_iter()
+            .map(|interface| interface.ip)
+            .collect();
+        match self.multicast_interfaces.iter().find(|ip| !local.contains(ip)) {
+            Some(ip) => Err(PeerDiscoveryError::NetworkInterfaceError(format!(
+                "No local interface has address {}", ip
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Make sure the advertised device type is one of `allowed_device_types`
+    fn validate_device_type(&self) -> Result<(), PeerDiscoveryError> {
+        let Some(allowed) = &self.allowed_device_types else {
+            return Ok(());
+        };
+
+        // `properties` overrides the profile, same as when registering
+        let device_type = self.properties.get(DEVICE_TYPE_PROPERTY)
+            .cloned()
+            .or_else(|| self.profile.as_ref().map(|profile| profile.device_type.to_string()));
+        match device_type {
+            Some(device_type) if !allowed.contains(&device_type) => Err(PeerDiscoveryError::InvalidDeviceType(format!(
+                "'{}' is not one of the allowed device types", device_type
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the local IP address for service registration
+    async fn ip_address(&self) -> Result<IpAddr, PeerDiscoveryError> {
+        if let Some(bind_ip) = self.bind_ip {
+            return Ok(bind_ip);
+        }
+
+        if let Some(interface_name) = &self.interface_name {
+            return self.interface_ip(interface_name);
+        }
+
+        // Pick the best non-loopback address according to the IP preference
+        let addresses: Vec<IpAddr> = local_interfaces()?
+            .into_iter()
+            .filter(|interface| !interface.is_loopback)
+            .map(|interface| interface.ip)
+            .collect();
+
+        self.ip_preference.select(&addresses).ok_or_else(|| {
+            PeerDiscoveryError::NetworkInterfaceError(
+                "No suitable network interface found".to_string()
+            )
+        })
+    }
+
+    /// Addresses to register, see `IpPreference::dual_stack`
+    async fn ip_addresses(&self) -> Result<Vec<IpAddr>, PeerDiscoveryError> {
+        let primary = self.ip_address().await?;
+        if self.bind_ip.is_some() {
+            return Ok(vec![primary]);
+        }
+
+        let candidates: Vec<IpAddr> = local_interfaces()?
+            .into_iter()
+            .filter(|interface| match &self.interface_name {
+                Some(interface_name) => interface.name == *interface_name,
+                None => !interface.is_loopback,
+            })
+            .map(|interface| interface.ip)
+            .collect();
+
+        Ok(self.ip_preference.dual_stack(primary, &candidates))
+    }
+
+    /// Best address of a named interface according to the IP preference
+    fn interface_ip(&self, interface_name: &str) -> Result<IpAddr, PeerDiscoveryError> {
+        let addresses: Vec<IpAddr> = local_interfaces()?
+            .into_iter()
+            .filter(|interface| interface.name == interface_name)
+            .map(|interface| interface.ip)
+            .collect();
+
+        if addresses.is_empty() {
+            return Err(PeerDiscoveryError::NetworkInterfaceError(format!(
+                "Network interface {:?} not found", interface_name
+            )));
+        }
+
+        self.ip_preference.select(&addresses).ok_or_else(|| {
+            PeerDiscoveryError::NetworkInterfaceError(format!(
+                "Network interface {:?} has no address matching {:?}",
+                interface_name, self.ip_preference
+            ))
+        })
+    }
+}
+
+/// Canonical form of a service fullname for comparisons: ASCII-lowercased with exactly one trailing dot
+///
+/// DNS names are case-insensitive and the trailing dot is optional, so resolve and removal
+/// events for the same service may not spell its fullname the same way.
+pub fn normalize_fullname(fullname: &str) -> String {
+    let mut normalized = fullname.trim_end_matches('.').to_ascii_lowercase();
+    normalized.push('.');
+    normalized
+}
+
+/// Check that a service type has the `_name._proto.local.` shape mDNS expects
+pub fn validate_service_type(service_type: &str) -> Result<(), PeerDiscoveryError> {
+    let invalid = |reason: &str| {
+        PeerDiscoveryError::InvalidServiceType(format!(
+            "{:?} {}, expected something like \"_qopyapp._tcp.local.\"",
+            service_type, reason
+        ))
+    };
+
+    let labels: Vec<&str> = service_type
+        .strip_suffix(".local.")
+        .ok_or_else(|| invalid("must end with \".local.\""))?
+        .split('.')
+        .collect();
+
+    let [name, proto] = labels.as_slice() else {
+        return Err(invalid("must have exactly a service name and a protocol label"));
+    };
+
+    let service = name.strip_prefix('_')
+        .ok_or_else(|| invalid("service name must start with an underscore"))?;
+    if service.is_empty() || service.len() > 15 {
+        return Err(invalid("service name must be 1-15 characters"));
+    }
+    if !service.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(invalid("service name may only contain letters, digits and hyphens"));
+    }
+
+    if *proto != "_tcp" && *proto != "_udp" {
+        return Err(invalid("protocol must be \"_tcp\" or \"_udp\""));
+    }
+
+    Ok(())
+}
+
+/// Utility function to get all available network interfaces
+pub async fn get_network_interfaces() -> Result<Vec<NetworkInterface>, PeerDiscoveryError> {
+    local_interfaces()
+}
+
+/// Address family filter for `get_usable_interfaces`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpFamily {
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    /// Whether an address belongs to this family
+    pub fn matches(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::Ipv4 => ip.is_ipv4(),
+            IpFamily::Ipv6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// Whether any of `interfaces` is up and able to multicast
+pub(crate) fn multicast_available(interfaces: &[NetworkInterface]) -> bool {
+    interfaces.iter().any(|interface| interface.is_up && interface.supports_multicast)
+}
+
+/// Whether `ip` is link-local (169.254.0.0/16 or fe80::/10), only reachable on its own segment
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+/// Interfaces a user could sensibly bind to: up, not loopback, and of the requested family
+pub async fn get_usable_interfaces(family: IpFamily) -> Result<Vec<NetworkInterface>, PeerDiscoveryError> {
+    Ok(usable_interfaces(local_interfaces()?, family))
+}
+
+fn usable_interfaces(interfaces: Vec<NetworkInterface>, family: IpFamily) -> Vec<NetworkInterface> {
+    interfaces.into_iter()
+        .filter(|interface| interface.is_up && !interface.is_loopback && family.matches(&interface.ip))
+        .collect()
+}
+
+/// Enumerate the local network interfaces for the current platform
+#[cfg(not(unix))]
+pub(crate) fn local_interfaces() -> Result<Vec<NetworkInterface>, PeerDiscoveryError> {
+    let interfaces = get_if_addrs::get_if_addrs().map_err(|e| {
+        PeerDiscoveryError::NetworkInterfaceError(e.to_string())
+    })?;
+
+    Ok(interfaces.into_iter()
+        .map(|interface| NetworkInterface {
+            name: interface.name.clone(),
+            ip: interface.ip(),
+            is_loopback: interface.is_loopback(),
+            // get_if_addrs only reports configured addresses and has no link state or flags
+            is_up: true,
+            supports_multicast: true,
+        })
+        .collect())
+}
+
+/// Enumerate the local network interfaces for the current platform
+#[cfg(unix)]
+pub(crate) fn local_interfaces() -> Result<Vec<NetworkInterface>, PeerDiscoveryError> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+
+    // SAFETY: on success getifaddrs hands us a linked list that we free below
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return Err(PeerDiscoveryError::NetworkInterfaceError(
+            std::io::Error::last_os_error().to_string()
+        ));
+    }
+
+    let mut result = Vec::new();
+    let mut current = ifaddrs;
+    while !current.is_null() {
+        // SAFETY: `current` is a valid node of the list returned by getifaddrs
+        let ifa = unsafe { &*current };
+        current = ifa.ifa_next;
+
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+
+        // SAFETY: the address family tells us which sockaddr layout `ifa_addr` points to
+        let ip = match unsafe { (*ifa.ifa_addr).sa_family } as libc::c_int {
+            libc::AF_INET => {
+                let addr = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+                IpAddr::V4(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)))
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in6) };
+                IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr))
+            }
+            _ => continue,
+        };
+
+        // SAFETY: ifa_name is a NUL-terminated string owned by the list
+        let name = unsafe { CStr::from_ptr(ifa.ifa_name) }.to_string_lossy().into_owned();
+
+        result.push(NetworkInterface {
+            name,
+            ip,
+            is_loopback: (ifa.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint) != 0,
+            is_up: (ifa.ifa_flags & libc::IFF_UP as libc::c_uint) != 0,
+            supports_multicast: (ifa.ifa_flags & libc::IFF_MULTICAST as libc::c_uint) != 0,
+        });
+    }
+
+    // SAFETY: `ifaddrs` came from a successful getifaddrs call and is freed exactly once
+    unsafe { libc::freeifaddrs(ifaddrs) };
+
+    Ok(result)
+}
+
+/// Represents a network interface
+#[derive(Debug, Clone)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub ip: IpAddr,
+    pub is_loopback: bool,
+    /// Whether the interface is up, always true where the platform can't tell
+    pub is_up: bool,
+    /// Whether the interface can send and receive multicast, always true where the platform can't tell
+    pub supports_multicast: bool,
+}
+
+impl NetworkInterface {
+    pub fn is_ipv4(&self) -> bool {
+        self.ip.is_ipv4()
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.ip.is_ipv6()
+    }
+
+    /// Family of the interface address, never `IpFamily::Any`
+    pub fn family(&self) -> IpFamily {
+        match self.ip {
+            IpAddr::V4(_) => IpFamily::Ipv4,
+            IpAddr::V6(_) => IpFamily::Ipv6,
+        }
+    }
+
+    /// Whether the address is link-local, usually a poor choice to advertise since peers on
+    /// other segments can't reach it and IPv6 ones need a scope ID
+    pub fn is_link_local(&self) -> bool {
+        is_link_local(&self.ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::sleep;
+
+    fn test_peer(name: &str, ip: &str) -> Peer {
+        let ip: IpAddr = ip.parse().unwrap();
+        Peer {
+            id: name.to_string(),
+            name: name.to_string(),
+            hostname: format!("{}.local.", name),
+            ip,
+            addresses: vec![ip],
+            address_pending: false,
+            port: 8080,
+            service_type: "_qopyapp._tcp.local.".to_string(),
+            properties: HashMap::new(),
+            raw_properties: HashMap::new(),
+            compatible: true,
+            source: PeerSource::Mdns,
+            last_seen: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_dual_stack_registration() {
+        let v4: IpAddr = "192.168.1.10".parse().unwrap();
+        let v6: IpAddr = "2001:db8::10".parse().unwrap();
+        let link_local: IpAddr = "fe80::10".parse().unwrap();
+        let candidates = [link_local, v4, v6];
+
+        assert_eq!(IpPreference::PreferIpv4.dual_stack(v4, &candidates), vec![v4, v6]);
+        assert_eq!(IpPreference::PreferIpv6.dual_stack(v6, &candidates), vec![v6, v4]);
+        assert_eq!(IpPreference::Ipv4Only.dual_stack(v4, &candidates), vec![v4]);
+        assert_eq!(IpPreference::Ipv6Only.dual_stack(v6, &candidates), vec![v6]);
+        assert_eq!(IpPreference::PreferIpv4.dual_stack(v4, &[v4, link_local]), vec![v4]);
+
+        let addresses = IpPreference::PreferIpv4.dual_stack(v4, &candidates);
+        let info = ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            "dual-stack",
+            "dual-stack.local.",
+            &addresses[..],
+            8080,
+            None,
+        ).unwrap();
+        assert_eq!(info.get_addresses().len(), 2);
+        assert_eq!(IpPreference::PreferIpv4.select(info.get_addresses()), Some(v4));
+    }
+
+    #[test]
+    fn test_usable_interfaces() {
+        let interface = |name: &str, ip: &str, is_loopback: bool, is_up: bool| NetworkInterface {
+            name: name.to_string(),
+            ip: ip.parse().unwrap(),
+            is_loopback,
+            is_up,
+            supports_multicast: true,
+        };
+        let interfaces = vec![
+            interface("lo", "127.0.0.1", true, true),
+            interface("lo", "::1", true, true),
+            interface("eth0", "192.168.1.10", false, true),
+            interface("eth0", "fe80::10", false, true),
+            interface("wlan0", "10.0.0.5", false, false),
+        ];
+
+        let names = |interfaces: Vec<NetworkInterface>| {
+            interfaces.into_iter().map(|interface| interface.ip.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(usable_interfaces(interfaces.clone(), IpFamily::Any)), vec!["192.168.1.10", "fe80::10"]);
+        assert_eq!(names(usable_interfaces(interfaces.clone(), IpFamily::Ipv4)), vec!["192.168.1.10"]);
+        assert_eq!(names(usable_interfaces(interfaces, IpFamily::Ipv6)), vec!["fe80::10"]);
+    }
+
+    #[test]
+    fn test_interface_family() {
+        let interface = |ip: &str| NetworkInterface {
+            name: "eth0".to_string(),
+            ip: ip.parse().unwrap(),
+            is_loopback: false,
+            is_up: true,
+            supports_multicast: true,
+        };
+
+        let v4 = interface("192.168.1.10");
+        assert!(v4.is_ipv4() && !v4.is_ipv6());
+        assert_eq!(v4.family(), IpFamily::Ipv4);
+        assert!(!v4.is_link_local());
+
+        let v6 = interface("2001:db8::10");
+        assert!(v6.is_ipv6() && !v6.is_ipv4());
+        assert_eq!(v6.family(), IpFamily::Ipv6);
+        assert!(!v6.is_link_local());
+
+        assert!(interface("169.254.12.1").is_link_local());
+        assert!(interface("fe80::10").is_link_local());
+        assert!(interface("febf::1").is_link_local());
+        assert!(!interface("fec0::1").is_link_local());
+    }
+
+    #[tokio::test]
+    async fn test_interface_name_selection() {
+        let loopback = local_interfaces().unwrap()
+            .into_iter()
+            .find(|interface| interface.is_loopback && interface.ip.is_ipv4())
+            .expect("no IPv4 loopback interface");
+
+        let config = DiscoveryConfig::builder()
+            .interface_name(loopback.name)
+            .ip_preference(IpPreference::PreferIpv4)
+            .build();
+        assert_eq!(config.ip_address().await.unwrap(), loopback.ip);
+
+        let config = DiscoveryConfig::builder()
+            .interface_name("qopyapp-missing0")
+            .build();
+        assert!(matches!(
+            config.ip_address().await,
+            Err(PeerDiscoveryError::NetworkInterfaceError(message)) if message.contains("not found")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_creation() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config);
+        assert!(discovery.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_start_stop() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+        
+        // Start the service
+        assert!(discovery.start().await.is_ok());
+        discovery.wait_until_ready(Duration::from_secs(1)).await.unwrap();
         
         // Stop the service
         assert!(discovery.stop().await.is_ok());
     }
 
     #[tokio::test]
-    async fn test_peer_discovery_subscription() {
-        let config = DiscoveryConfig::default();
-        let discovery = PeerDiscovery::new(config).unwrap();
-        
+    async fn test_peer_discovery_is_running() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        assert!(!discovery.is_running().await);
+        discovery.start().await.unwrap();
+        assert!(discovery.is_running().await);
+        discovery.stop().await.unwrap();
+        assert!(!discovery.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_subscription() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+        
+        let mut receiver = discovery.subscribe();
+        
+        // Start the service
+        discovery.start().await.unwrap();
+        
+        // Should receive ServiceStarted event
+        let event = receiver.recv().await.unwrap();
+        assert!(matches!(event, PeerEvent::ServiceStarted));
+        
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_failure_rolls_back() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // Registration fails once the daemon is gone
+        discovery.transport().unwrap().shutdown().await.unwrap();
+
+        assert!(discovery.start().await.is_err());
+        assert!(!discovery.is_running().await);
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_can_be_retried() {
+        let taken = std::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .port(port)
+            .port_range(port..=port)
+            .build()).unwrap();
+
+        // Every candidate port is taken, so the first start fails
+        assert!(discovery.start().await.is_err());
+        assert!(!discovery.is_running().await);
+
+        // A second start must really try again rather than report the stale flag
+        drop(taken);
+        discovery.start().await.unwrap();
+        assert!(discovery.is_running().await);
+        assert_eq!(discovery.port().await, port);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_event_stream() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        let stream = discovery.event_stream();
+
+        discovery.start().await.unwrap();
+        discovery.stop().await.unwrap();
+
+        let events: Vec<PeerEvent> = stream.take(2).collect().await;
+        assert!(matches!(events[0], PeerEvent::ServiceStarted));
+        assert!(matches!(events[1], PeerEvent::ServiceStopped));
+    }
+
+    #[tokio::test]
+    async fn test_drain_events() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let (events, started) = tokio::join!(
+            discovery.drain_events(Duration::from_millis(500)),
+            async {
+                sleep(Duration::from_millis(50)).await;
+                discovery.start().await
+            }
+        );
+
+        started.unwrap();
+        assert!(events.iter().any(|event| matches!(event, PeerEvent::ServiceStarted)));
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recent_events_history() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .event_history_size(3)
+            .build()).unwrap();
+
+        let peers: Vec<Peer> = (0..5).map(|i| test_peer(&format!("peer-{}", i), "192.168.1.10")).collect();
+        for peer in &peers {
+            discovery.peer_sender.send(PeerEvent::PeerDiscovered(peer.clone()));
+        }
+
+        let names = |events: Vec<(Instant, PeerEvent)>| {
+            events.into_iter()
+                .map(|(_, event)| match event {
+                    PeerEvent::PeerDiscovered(peer) => peer.name,
+                    other => panic!("unexpected event {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(discovery.recent_events(10)), vec!["peer-2", "peer-3", "peer-4"]);
+        assert_eq!(names(discovery.recent_events(1)), vec!["peer-4"]);
+
+        let disabled = PeerDiscovery::new(DiscoveryConfig::builder()
+            .event_history_size(0)
+            .build()).unwrap();
+        disabled.peer_sender.send(PeerEvent::ServiceStarted);
+        assert!(disabled.recent_events(10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recv_skipping_lag() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .event_buffer_size(2)
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // Overflow the channel so the receiver lags
+        for i in 0..5 {
+            assert!(discovery.peer_sender.send(PeerEvent::PeerDiscovered(test_peer(&format!("peer-{}", i), "192.168.1.10"))));
+        }
+
+        let name = |event: Option<PeerEvent>| match event {
+            Some(PeerEvent::PeerDiscovered(peer)) => peer.name,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_eq!(name(recv_skipping_lag(&mut receiver).await), "peer-3");
+        assert_eq!(name(recv_skipping_lag(&mut receiver).await), "peer-4");
+
+        assert!(discovery.peer_sender.send(PeerEvent::PeerDiscovered(test_peer("peer-5", "192.168.1.10"))));
+        assert_eq!(name(recv_skipping_lag(&mut receiver).await), "peer-5");
+    }
+
+    #[tokio::test]
+    async fn test_peer_changes_skip_lifecycle_events() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let changes = discovery.subscribe_peer_changes();
+
+        let peer = test_peer("peer", "192.168.1.10");
+        for event in [
+            PeerEvent::ServiceStarted,
+            PeerEvent::PeerDiscovered(peer.clone()),
+            PeerEvent::PeerUpdated(peer.clone()),
+            PeerEvent::ServiceStopped,
+            PeerEvent::PeerLost(peer),
+        ] {
+            assert!(discovery.peer_sender.send(event));
+        }
+
+        let changes: Vec<PeerChange> = changes.take(2).collect().await;
+        assert!(matches!(&changes[0], PeerChange::Added(peer) if peer.name == "peer"));
+        assert!(matches!(&changes[1], PeerChange::Removed(peer) if peer.name == "peer"));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_timeout_validation() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .max_discovery_timeout(Duration::from_secs(60))
+            .build()).unwrap();
+
+        assert!(matches!(
+            discovery.discover_peers(Some(Duration::ZERO)).await,
+            Err(PeerDiscoveryError::DiscoveryTimeout(_))
+        ));
+        assert!(matches!(
+            discovery.discover_peers(Some(Duration::from_secs(61))).await,
+            Err(PeerDiscoveryError::DiscoveryTimeout(_))
+        ));
+        assert!(!discovery.is_running().await);
+
+        assert!(discovery.discover_peers(Some(Duration::from_millis(100))).await.is_ok());
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discover_peers_strict_times_out() {
+        let config = DiscoveryConfig::builder()
+            .service_type("_qopyapp-none._tcp.local.")
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        // Nobody else advertises this service type, so the requirement can't be met
+        let result = discovery.discover_peers_strict(10, Some(Duration::from_millis(500))).await;
+        assert!(matches!(result, Err(PeerDiscoveryError::DiscoveryTimeout(_))));
+
+        // Zero required peers is satisfied immediately
+        let peers = discovery.discover_peers_strict(0, Some(Duration::from_secs(30))).await.unwrap();
+        assert!(peers.is_empty());
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discover_peers_early_exit() {
+        let advertiser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-early")
+            .instance_id("test-early")
+            .build()).unwrap();
+        let browser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-early-browser")
+            .instance_id("test-early-browser")
+            .early_exit_after(Some(1))
+            .build()).unwrap();
+
+        advertiser.start().await.unwrap();
+
+        let timeout_duration = Duration::from_secs(10);
+        let started = std::time::Instant::now();
+        let peers = browser.discover_peers(Some(timeout_duration)).await.unwrap();
+
+        assert!(!peers.is_empty());
+        assert!(started.elapsed() < timeout_duration);
+
+        advertiser.stop().await.unwrap();
+        browser.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_service_type() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        discovery.add_service_type("_qopyprint._tcp.local.".to_string()).await.unwrap();
+        // Adding the same type twice is a no-op
+        discovery.add_service_type("_qopyprint._tcp.local.".to_string()).await.unwrap();
+        assert!(discovery.add_service_type("printer".to_string()).await.is_err());
+
+        assert_eq!(
+            *discovery.browse_types.read().await,
+            vec!["_qopyapp._tcp.local.".to_string(), "_qopyprint._tcp.local.".to_string()]
+        );
+
+        discovery.start().await.unwrap();
+        discovery.add_service_type("_qopycompanion._tcp.local.".to_string()).await.unwrap();
+        assert_eq!(discovery.browse_types.read().await.len(), 3);
+        assert!(discovery.stop().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_peer_lookups() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let mut laptop = test_peer("laptop", "192.168.1.10");
+        laptop.addresses.push("fe80::10".parse().unwrap());
+        laptop.properties.insert("device_type".to_string(), "laptop".to_string());
+        let mut phone = test_peer("phone", "192.168.1.11");
+        phone.properties.insert("device_type".to_string(), "phone".to_string());
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            peers.insert(laptop.id.clone(), laptop);
+            peers.insert(phone.id.clone(), phone);
+        }
+
+        let by_ip = discovery.get_peer_by_ip("192.168.1.11".parse().unwrap()).await;
+        assert_eq!(by_ip.map(|peer| peer.name), Some("phone".to_string()));
+        let by_secondary_ip = discovery.get_peer_by_ip("fe80::10".parse().unwrap()).await;
+        assert_eq!(by_secondary_ip.map(|peer| peer.name), Some("laptop".to_string()));
+        assert!(discovery.get_peer_by_ip("10.0.0.1".parse().unwrap()).await.is_none());
+
+        let laptops = discovery.get_peers_by_device_type("laptop").await;
+        assert_eq!(laptops.len(), 1);
+        assert_eq!(laptops[0].name, "laptop");
+        assert!(discovery.get_peers_by_device_type("tablet").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_peers_paged() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for (i, name) in ["delta", "alpha", "echo", "charlie", "bravo"].iter().enumerate() {
+                peers.insert(name.to_string(), test_peer(name, &format!("192.168.1.{}", 10 + i)));
+            }
+        }
+        assert_eq!(discovery.peer_count().await, 5);
+
+        let names = |peers: Vec<Peer>| peers.into_iter().map(|peer| peer.name).collect::<Vec<_>>();
+        assert_eq!(names(discovery.get_peers_paged(0, 2).await), vec!["alpha", "bravo"]);
+        assert_eq!(names(discovery.get_peers_paged(2, 2).await), vec!["charlie", "delta"]);
+        assert_eq!(names(discovery.get_peers_paged(4, 2).await), vec!["echo"]);
+        assert!(discovery.get_peers_paged(5, 2).await.is_empty());
+        assert!(discovery.get_peers_paged(100, 2).await.is_empty());
+        assert!(discovery.get_peers_paged(0, 0).await.is_empty());
+        assert_eq!(discovery.get_peers_paged(0, usize::MAX).await.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_peers_sorted() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let now = SystemTime::now();
+        let mut bravo = test_peer("bravo", "192.168.1.5");
+        bravo.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "phone".to_string());
+        bravo.last_seen = now - Duration::from_secs(10);
+        let mut alpha = test_peer("alpha", "192.168.1.20");
+        alpha.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "tablet".to_string());
+        alpha.last_seen = now - Duration::from_secs(60);
+        let mut charlie = test_peer("charlie", "192.168.1.10");
+        charlie.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "desktop".to_string());
+        charlie.last_seen = now;
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for peer in [bravo, alpha, charlie] {
+                peers.insert(peer.id.clone(), peer);
+            }
+        }
+
+        let names = |peers: Vec<Peer>| peers.into_iter().map(|peer| peer.name).collect::<Vec<_>>();
+        assert_eq!(names(discovery.get_peers_sorted(PeerSort::ByName).await), vec!["alpha", "bravo", "charlie"]);
+        assert_eq!(names(discovery.get_peers_sorted(PeerSort::ByIp).await), vec!["bravo", "charlie", "alpha"]);
+        assert_eq!(names(discovery.get_peers_sorted(PeerSort::ByLastSeen).await), vec!["charlie", "bravo", "alpha"]);
+        assert_eq!(names(discovery.get_peers_sorted(PeerSort::ByDeviceType).await), vec!["charlie", "bravo", "alpha"]);
+    }
+
+    #[tokio::test]
+    async fn test_find_peers() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let mut laptop = test_peer("Alice-Laptop._qopyapp._tcp.local.", "192.168.1.10");
+        laptop.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "laptop".to_string());
+        let mut phone = test_peer("Bob-Phone._qopyapp._tcp.local.", "192.168.1.11");
+        phone.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "phone".to_string());
+        let mut desktop = test_peer("alice-desktop._qopyapp._tcp.local.", "192.168.1.12");
+        desktop.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "desktop".to_string());
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for peer in [laptop, phone, desktop] {
+                peers.insert(peer.id.clone(), peer);
+            }
+        }
+
+        let find = |query: &'static str| {
+            let discovery = discovery.clone();
+            async move {
+                let mut names: Vec<String> = discovery.find_peers(query).await
+                    .into_iter()
+                    .map(|peer| peer.name)
+                    .collect();
+                names.sort();
+                names
+            }
+        };
+
+        assert_eq!(find("Bob-Phone").await, vec!["Bob-Phone._qopyapp._tcp.local."]);
+        assert_eq!(find("ALICE").await, vec!["Alice-Laptop._qopyapp._tcp.local.", "alice-desktop._qopyapp._tcp.local."]);
+        assert_eq!(find("lap").await, vec!["Alice-Laptop._qopyapp._tcp.local."]);
+        assert_eq!(find("PHONE").await, vec!["Bob-Phone._qopyapp._tcp.local."]);
+        assert!(find("qopyapp").await.is_empty());
+        assert!(find("carol").await.is_empty());
+        assert_eq!(find("").await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_peer() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let is_phone = |peer: &Peer| peer.device_type() == DeviceType::Phone;
+
+        let mut laptop = test_peer("laptop", "192.168.1.10");
+        laptop.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "laptop".to_string());
+        discovery.discovered_peers.write().await.insert(laptop.id.clone(), laptop);
+
+        assert!(matches!(
+            discovery.wait_for_peer(is_phone, Duration::from_millis(100)).await,
+            Err(PeerDiscoveryError::DiscoveryTimeout(_))
+        ));
+
+        // A phone showing up while waiting is returned
+        let mut phone = test_peer("phone", "192.168.1.11");
+        phone.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), "phone".to_string());
+        let (found, _) = tokio::join!(
+            discovery.wait_for_peer(is_phone, Duration::from_secs(5)),
+            async {
+                sleep(Duration::from_millis(50)).await;
+                discovery.discovered_peers.write().await.insert(phone.id.clone(), phone.clone());
+                assert!(discovery.peer_sender.send(PeerEvent::PeerDiscovered(phone.clone())));
+            }
+        );
+        assert_eq!(found.unwrap().name, "phone");
+
+        // Already known peers match right away
+        let found = discovery.wait_for_peer(is_phone, Duration::from_millis(100)).await;
+        assert_eq!(found.unwrap().name, "phone");
+    }
+
+    #[tokio::test]
+    async fn test_clear_peers() {
+        // A service type of its own so no other test's peers show up
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_type("_qopyclear._tcp.local.")
+            .service_name("qopyapp-test-clear")
+            .instance_id("test-clear")
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for name in ["one", "two"] {
+                peers.insert(name.to_string(), test_peer(name, "192.168.1.10"));
+            }
+        }
+        discovery.clear_peers(true).await;
+
+        assert!(discovery.get_peers().await.is_empty());
+        assert!(discovery.is_running().await);
+
+        let mut lost = Vec::new();
+        while lost.len() < 2 {
+            if let PeerEvent::PeerLost(peer) = receiver.recv().await.unwrap() {
+                lost.push(peer.name);
+            }
+        }
+        lost.sort();
+        assert_eq!(lost, vec!["one", "two"]);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_restart() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        let mut receiver = discovery.subscribe();
+        discovery.start().await.unwrap();
+        discovery.discovered_peers.write().await.insert("stale".to_string(), test_peer("stale", "192.168.1.10"));
+
+        discovery.restart().await.unwrap();
+
+        // Only look at lifecycle events, peers may be resolved in between
+        let mut lifecycle = Vec::new();
+        while lifecycle.len() < 3 {
+            if let event @ (PeerEvent::ServiceStarted | PeerEvent::ServiceStopped) = receiver.recv().await.unwrap() {
+                lifecycle.push(event);
+            }
+        }
+        assert!(matches!(lifecycle[0], PeerEvent::ServiceStarted));
+        assert!(matches!(lifecycle[1], PeerEvent::ServiceStopped));
+        assert!(matches!(lifecycle[2], PeerEvent::ServiceStarted));
+        assert!(discovery.get_peer_by_id("stale").await.is_none());
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reannounce_on_address_change() {
+        let config = DiscoveryConfig::builder()
+            .auto_reannounce_on_network_change(true)
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        discovery.start().await.unwrap();
+        let registered = discovery.registered_ip.read().await.unwrap();
+        assert!(!discovery.reannounce_if_address_changed().await.unwrap());
+
+        // Pretend we registered on an address we no longer have
+        let stale: IpAddr = "203.0.113.1".parse().unwrap();
+        *discovery.registered_ip.write().await = Some(stale);
+        let mut receiver = discovery.subscribe();
+
+        assert!(discovery.reannounce_if_address_changed().await.unwrap());
+        assert_eq!(*discovery.registered_ip.read().await, Some(registered));
+        assert!(matches!(
+            receiver.recv().await.unwrap(),
+            PeerEvent::LocalAddressChanged { previous, current } if previous == stale && current == registered
+        ));
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_unbounded_is_lossless() {
+        let config = DiscoveryConfig::builder()
+            .event_buffer_size(10)
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        let mut lossy = discovery.subscribe();
+        let mut lossless = discovery.subscribe_unbounded();
+
+        for i in 0..1000 {
+            discovery.peer_sender.send(PeerEvent::PeerLost(test_peer(&format!("peer-{}", i), "192.168.1.10")));
+        }
+
+        for i in 0..1000 {
+            match lossless.recv().await.unwrap() {
+                PeerEvent::PeerLost(peer) => assert_eq!(peer.name, format!("peer-{}", i)),
+                event => panic!("unexpected event: {:?}", event),
+            }
+        }
+        assert!(lossless.try_recv().is_err());
+
+        // The bounded broadcast receiver fell behind
+        assert!(matches!(lossy.recv().await, Err(broadcast::error::RecvError::Lagged(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_peer() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        // The first address refuses, the second one accepts
+        let mut peer = test_peer("listener", "127.0.0.1");
+        peer.port = port;
+        peer.addresses = vec!["::1".parse().unwrap(), "127.0.0.1".parse().unwrap()];
+
+        let stream = discovery.connect_to(&peer).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap().port(), port);
+
+        drop(listener);
+        assert!(matches!(
+            discovery.connect_to(&peer).await,
+            Err(PeerDiscoveryError::ConnectionFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_port_range_fallback() {
+        let first = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-port-a")
+            .port(45000)
+            .port_range(45000..=45010)
+            .build()).unwrap();
+        let second = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-port-b")
+            .port(45000)
+            .port_range(45000..=45010)
+            .build()).unwrap();
+
+        first.start().await.unwrap();
+        second.start().await.unwrap();
+
+        assert!((45000..=45010).contains(&first.port().await));
+        assert!((45000..=45010).contains(&second.port().await));
+        assert_ne!(first.port().await, second.port().await);
+
+        first.stop().await.unwrap();
+        second.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_peer() {
+        let config = DiscoveryConfig::builder()
+            .service_name("qopyapp-test-local")
+            .instance_id("test-local")
+            .port(9090)
+            .property("device_type", "desktop")
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        assert!(discovery.local_peer().await.is_none());
+        discovery.start().await.unwrap();
+
+        let local = discovery.local_peer().await.unwrap();
+        assert_eq!(local.id, "test-local");
+        assert_eq!(local.name, "qopyapp-test-local._qopyapp._tcp.local.");
+        assert_eq!(local.port, 9090);
+        assert_eq!(local.service_type, "_qopyapp._tcp.local.");
+        assert_eq!(local.device_type(), DeviceType::Desktop);
+        assert_eq!(local.properties.get(INSTANCE_ID_PROPERTY), Some(&"test-local".to_string()));
+
+        discovery.stop().await.unwrap();
+        assert!(discovery.local_peer().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_own_service_is_filtered() {
+        let discovery = loopback_discovery("test-own-service");
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // Our own record arrives first, the next peer proves it was handled and dropped
+        let own = discovery.service_info().await.unwrap();
+        discovery.inject_service_event(ServiceEvent::ServiceResolved(own)).unwrap();
+        discovery.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(9000, "1"))).unwrap();
+
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.id == "loopback-peer"));
+        let peers = discovery.get_peers().await;
+        assert_eq!(peers.len(), 1);
+        assert!(peers.iter().all(|peer| peer.id != "test-own-service"));
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_include_self() {
+        let config = DiscoveryConfig::builder()
+            .service_type("_qopyapp-self._tcp.local.")
+            .include_self(true)
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        let own = discovery.service_info().await.unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(own)).await.unwrap();
+
+        let peers = discovery.get_peers().await;
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].id, local_instance_id());
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_get_peers() {
+        let config = DiscoveryConfig::default();
+        let discovery = PeerDiscovery::new(config).unwrap();
+        
+        // Initially no peers
+        let peers = discovery.get_peers().await;
+        assert!(peers.is_empty());
+        
+        discovery.start().await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        discovery.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_config_builder_defaults() {
+        let config = DiscoveryConfig::builder()
+            .service_name("x")
+            .build();
+        let default = DiscoveryConfig::default();
+
+        assert_eq!(config.service_name, "x");
+        assert_eq!(config.service_type, default.service_type);
+        assert_eq!(config.port, default.port);
+        assert_eq!(config.discovery_timeout, default.discovery_timeout);
+        assert_eq!(config.announce_interval, default.announce_interval);
+        assert!(config.properties.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_service_info_uses_bind_ip() {
+        let bind_ip: IpAddr = "10.1.2.3".parse().unwrap();
+        let config = DiscoveryConfig::builder()
+            .bind_ip(bind_ip)
+            .build();
+        let discovery = PeerDiscovery::new(config).unwrap();
+
+        let service_info = discovery.service_info().await.unwrap();
+        let addresses: Vec<IpAddr> = service_info.get_addresses().iter().copied().collect();
+        assert_eq!(addresses, vec![bind_ip]);
+    }
+
+    #[tokio::test]
+    async fn test_peers_json_round_trip() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let mut laptop = test_peer("laptop", "192.168.1.10");
+        laptop.addresses.push("fe80::10".parse().unwrap());
+        laptop.properties.insert("device_type".to_string(), "laptop".to_string());
+        let phone = test_peer("phone", "fe80::11");
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            peers.insert(laptop.id.clone(), laptop.clone());
+            peers.insert(phone.id.clone(), phone.clone());
+        }
+
+        let json = discovery.peers_as_json().await.unwrap();
+        let mut parsed = Peer::list_from_json(&json).unwrap();
+        parsed.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(parsed, vec![laptop.clone(), phone]);
+
+        let single = Peer::from_json(&serde_json::to_string(&laptop).unwrap()).unwrap();
+        assert_eq!(single, laptop);
+        assert!(matches!(Peer::from_json("{}"), Err(PeerDiscoveryError::SerializationError(_))));
+    }
+
+    #[test]
+    fn test_device_type_parsing() {
+        assert_eq!("desktop".parse::<DeviceType>().unwrap(), DeviceType::Desktop);
+        assert_eq!("Laptop".parse::<DeviceType>().unwrap(), DeviceType::Laptop);
+        assert_eq!(" phone ".parse::<DeviceType>().unwrap(), DeviceType::Phone);
+        assert_eq!("tablet".parse::<DeviceType>().unwrap(), DeviceType::Tablet);
+        assert_eq!("server".parse::<DeviceType>().unwrap(), DeviceType::Server);
+        assert_eq!("monitor".parse::<DeviceType>().unwrap(), DeviceType::Unknown("monitor".to_string()));
+
+        for device_type in [DeviceType::Desktop, DeviceType::Phone, DeviceType::Unknown("monitor".to_string())] {
+            assert_eq!(device_type.to_string().parse::<DeviceType>().unwrap(), device_type);
+        }
+    }
+
+    #[test]
+    fn test_peer_device_type() {
+        let mut peer = test_peer("peer", "192.168.1.10");
+        assert_eq!(peer.device_type(), DeviceType::Unknown("unknown".to_string()));
+
+        peer.properties = DiscoveryConfig::default().with_device_type(DeviceType::Tablet).properties;
+        assert_eq!(peer.device_type(), DeviceType::Tablet);
+    }
+
+    #[test]
+    fn test_freshness_buckets() {
+        assert_eq!(Freshness::from_age(Duration::ZERO), Freshness::Fresh);
+        assert_eq!(Freshness::from_age(Duration::from_millis(29_999)), Freshness::Fresh);
+        assert_eq!(Freshness::from_age(Duration::from_secs(30)), Freshness::Recent);
+        assert_eq!(Freshness::from_age(Duration::from_millis(119_999)), Freshness::Recent);
+        assert_eq!(Freshness::from_age(Duration::from_secs(120)), Freshness::Stale);
+
+        let mut peer = test_peer("peer", "192.168.1.10");
+        assert_eq!(peer.freshness(), Freshness::Fresh);
+        peer.last_seen = SystemTime::now() - Duration::from_secs(60);
+        assert_eq!(peer.freshness(), Freshness::Recent);
+        peer.last_seen = SystemTime::now() - Duration::from_secs(600);
+        assert_eq!(peer.freshness(), Freshness::Stale);
+        assert!(Freshness::Fresh < Freshness::Stale);
+    }
+
+    #[test]
+    fn test_peer_socket_addrs() {
+        let mut peer = test_peer("multi", "192.168.1.10");
+        peer.port = 9000;
+        peer.addresses.push("fe80::10".parse().unwrap());
+
+        assert_eq!(peer.primary_socket_addr(), "192.168.1.10:9000".parse().unwrap());
+        assert_eq!(peer.socket_addrs(), vec![
+            "192.168.1.10:9000".parse::<SocketAddr>().unwrap(),
+            "[fe80::10]:9000".parse().unwrap(),
+        ]);
+
+        peer.addresses.clear();
+        assert_eq!(peer.socket_addrs(), vec![peer.primary_socket_addr()]);
+    }
+
+    #[test]
+    fn test_capabilities_round_trip() {
+        let config = DiscoveryConfig::default().with_capabilities(&["file_sharing", "chat"]);
+        assert_eq!(config.properties.get(CAPABILITIES_PROPERTY), Some(&"file_sharing,chat".to_string()));
+
+        let mut peer = test_peer("peer", "192.168.1.10");
+        peer.properties = config.properties;
+        assert_eq!(peer.capabilities(), vec!["file_sharing".to_string(), "chat".to_string()]);
+        assert!(peer.has_capability("chat"));
+        assert!(!peer.has_capability("printing"));
+    }
+
+    #[test]
+    fn test_capabilities_parsing() {
+        let mut peer = test_peer("peer", "192.168.1.10");
+        assert!(peer.capabilities().is_empty());
+
+        peer.properties.insert(CAPABILITIES_PROPERTY.to_string(), " file_sharing , ,chat,".to_string());
+        assert_eq!(peer.capabilities(), vec!["file_sharing".to_string(), "chat".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_service_type() {
+        assert!(validate_service_type("_qopyapp._tcp.local.").is_ok());
+        assert!(validate_service_type("_http._tcp.local.").is_ok());
+        assert!(validate_service_type("_qopy-sync._udp.local.").is_ok());
+    }
+
+    #[test]
+    fn test_validate_service_type_rejects_malformed() {
+        for service_type in [
+            "qopyapp",
+            "_qopyapp._tcp.local",
+            "_qopyapp._tcp",
+            "qopyapp._tcp.local.",
+            "_qopyapp._sctp.local.",
+            "_._tcp.local.",
+            "_qopyapp.extra._tcp.local.",
+            "_qopy_app._tcp.local.",
+            "_averyveryverylongname._tcp.local.",
+        ] {
+            assert!(
+                matches!(validate_service_type(service_type), Err(PeerDiscoveryError::InvalidServiceType(_))),
+                "{} should be rejected",
+                service_type
+            );
+        }
+
+        let config = DiscoveryConfig::builder()
+            .service_type("qopyapp")
+            .build();
+        assert!(matches!(PeerDiscovery::new(config), Err(PeerDiscoveryError::InvalidServiceType(_))));
+    }
+
+    #[test]
+    fn test_jittered_interval_bounds() {
+        let interval = Duration::from_secs(30);
+        let jitter = Duration::from_secs(5);
+
+        let samples: Vec<Duration> = (0..1000).map(|_| jittered_interval(interval, jitter)).collect();
+        assert!(samples.iter().all(|sample| *sample >= interval - jitter && *sample <= interval + jitter));
+        // The samples should actually be spread out
+        assert!(samples.iter().any(|sample| *sample != samples[0]));
+
+        assert_eq!(jittered_interval(interval, Duration::ZERO), interval);
+        // Jitter larger than the interval never goes below zero
+        assert!(jittered_interval(Duration::from_secs(1), jitter) <= Duration::from_secs(6));
+    }
+
+    #[test]
+    fn test_ip_preference_select() {
+        let v4: IpAddr = "192.168.1.20".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let link_local: IpAddr = "fe80::1".parse().unwrap();
+        let both = [link_local, v6, v4];
+
+        assert_eq!(IpPreference::PreferIpv4.select(&both), Some(v4));
+        assert_eq!(IpPreference::PreferIpv6.select(&both), Some(v6));
+        assert_eq!(IpPreference::Ipv4Only.select(&both), Some(v4));
+        assert_eq!(IpPreference::Ipv6Only.select(&both), Some(v6));
+
+        // Link-local IPv6 loses to any routable address and is only picked as a last resort
+        assert_eq!(IpPreference::PreferIpv6.select(&[link_local, v4]), Some(v4));
+        assert_eq!(IpPreference::Ipv6Only.select(&[link_local]), Some(link_local));
+
+        // Fall back to the other family only when allowed
+        assert_eq!(IpPreference::PreferIpv4.select(&[v6]), Some(v6));
+        assert_eq!(IpPreference::Ipv4Only.select(&[v6]), None);
+        assert_eq!(IpPreference::Ipv6Only.select(&[v4]), None);
+    }
+
+    #[tokio::test]
+    async fn test_peer_exposes_all_addresses() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let info = ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            "multi-homed",
+            "multi-homed.local.",
+            "192.168.1.10,fe80::10",
+            8080,
+            None,
+        ).unwrap();
+
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+
+        let peers = discovery.discovered_peers.read().await;
+        let peer = peers.values().next().unwrap();
+        let v4: IpAddr = "192.168.1.10".parse().unwrap();
+        let v6: IpAddr = "fe80::10".parse().unwrap();
+        assert_eq!(peer.ip, v4);
+        assert_eq!(peer.addresses, vec![v4, v6]);
+    }
+
+    #[tokio::test]
+    async fn test_peer_hostname() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-hostname")
+            .bind_ip("127.0.0.1".parse().unwrap())
+            .build()).unwrap();
+
+        let info = ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            "named-host",
+            "named-host.local.",
+            "192.168.1.10",
+            8080,
+            None,
+        ).unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+
+        let peer = discovery.get_peers().await.pop().unwrap();
+        assert_eq!(peer.hostname, "named-host.local.");
+
+        discovery.start().await.unwrap();
+        assert_eq!(discovery.local_peer().await.unwrap().hostname, "qopyapp-test-hostname.local.");
+        discovery.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_peer_identity_and_content_eq() {
+        let peer = test_peer("laptop", "192.168.1.10");
+
+        // A re-resolve moments later is the same in every way that matters
+        let refreshed = Peer { last_seen: peer.last_seen + Duration::from_secs(5), ..peer.clone() };
+        assert!(peer.same_identity(&refreshed));
+        assert!(peer.content_eq(&refreshed));
+        assert_ne!(peer, refreshed);
+
+        // A changed property keeps the identity but not the content
+        let mut changed = peer.clone();
+        changed.properties.insert("version".to_string(), "2".to_string());
+        assert!(peer.same_identity(&changed));
+        assert!(!peer.content_eq(&changed));
+
+        let other = test_peer("phone", "192.168.1.10");
+        assert!(!peer.same_identity(&other));
+        assert!(!peer.content_eq(&other));
+    }
+
+    #[tokio::test]
+    async fn test_resolving_known_peer_emits_update() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for port in [8080, 8081] {
+            let info = ServiceInfo::new(
+                "_qopyapp._tcp.local.",
+                "moving-peer",
+                "moving-peer.local.",
+                "192.168.1.10",
+                port,
+                None,
+            ).unwrap();
+
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+        }
+
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.port == 8080));
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerUpdated(peer) if peer.port == 8081));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peers_keyed_by_instance_id() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // The same instance re-advertising under a new name stays a single peer
+        for name in ["old-name", "new-name"] {
+            let info = ServiceInfo::new(
+                "_qopyapp._tcp.local.",
+                name,
+                "renamed.local.",
+                "192.168.1.10",
+                8080,
+                &[(INSTANCE_ID_PROPERTY, "abc123")][..],
+            ).unwrap();
+
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+        }
+
+        {
+            let peers = discovery.discovered_peers.read().await;
+            assert_eq!(peers.len(), 1);
+            assert!(peers["abc123"].name.starts_with("new-name"));
+        }
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(_)));
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerUpdated(peer) if peer.id == "abc123"));
+
+        // Removal is reported by fullname
+        let fullname = discovery.discovered_peers.read().await["abc123"].name.clone();
+        discovery.handle_service_event(ServiceEvent::ServiceRemoved("_qopyapp._tcp.local.".to_string(), fullname)).await.unwrap();
+
+        assert!(discovery.discovered_peers.read().await.is_empty());
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerLost(peer) if peer.id == "abc123"));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_stats() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        assert_eq!(discovery.stats().await, DiscoveryStats::default());
+
+        for (name, port) in [("stats-a", 8080), ("stats-b", 8080), ("stats-a", 8081)] {
+            let info = ServiceInfo::new("_qopyapp._tcp.local.", name, "stats.local.", "192.168.1.10", port, None).unwrap();
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+        }
+        discovery.handle_service_event(ServiceEvent::ServiceRemoved(
+            "_qopyapp._tcp.local.".to_string(),
+            "stats-b._qopyapp._tcp.local.".to_string(),
+        )).await.unwrap();
+
+        // Peers without addresses are kept as pending and counted apart
+        let info = ServiceInfo::new("_qopyapp._tcp.local.", "stats-c", "stats.local.", "", 8080, None).unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+        discovery.peer_sender.send(PeerEvent::Error(PeerDiscoveryError::DiscoveryTimeout("stats".to_string())));
+
+        let stats = discovery.stats().await;
+        assert_eq!(stats.discovered, 2);
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.updated, 1);
+        assert_eq!(stats.lost, 1);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.current_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune_stale_peers() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        let fresh = test_peer("fresh", "192.168.1.10");
+        let mut stale = test_peer("stale", "192.168.1.11");
+        stale.last_seen = SystemTime::now() - Duration::from_secs(300);
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            peers.insert(fresh.id.clone(), fresh);
+            peers.insert(stale.id.clone(), stale);
+        }
+
+        let pruned = discovery.prune_stale_peers(Duration::from_secs(120)).await;
+
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].name, "stale");
+        assert!(discovery.discovered_peers.read().await.contains_key("fresh"));
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerLost(peer) if peer.name == "stale"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_peer_and_reachable_peers() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        let mut live = test_peer("live", "127.0.0.1");
+        live.port = listener.local_addr().unwrap().port();
+        let mut dead = test_peer("dead", "127.0.0.1");
+        dead.port = dead_port;
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            peers.insert(live.id.clone(), live.clone());
+            peers.insert(dead.id.clone(), dead.clone());
+        }
+
+        assert!(discovery.verify_peer(&live).await);
+        assert!(!discovery.verify_peer(&dead).await);
+
+        let reachable = discovery.get_reachable_peers().await;
+        assert_eq!(reachable.len(), 1);
+        assert_eq!(reachable[0].name, "live");
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .max_concurrent_connections(1)
+            .build()).unwrap();
+
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for name in ["first", "second", "third"] {
+                let mut peer = test_peer(name, "127.0.0.1");
+                peer.port = listener.local_addr().unwrap().port();
+                peers.insert(peer.id.clone(), peer);
+            }
+        }
+
+        // With the only slot taken, no check can connect
+        let slot = discovery.connection_permits.clone().acquire_owned().await.unwrap();
+        let checker = discovery.clone();
+        let checks = tokio::spawn(async move { checker.get_reachable_peers().await });
+        sleep(Duration::from_millis(200)).await;
+        assert!(!checks.is_finished());
+
+        // Released, the checks run one at a time through that single slot
+        drop(slot);
+        let reachable = tokio::time::timeout(Duration::from_secs(5), checks).await.unwrap().unwrap();
+        assert_eq!(reachable.len(), 3);
+        assert_eq!(discovery.connection_permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subtype_discovery() {
+        let sharer = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-sharer")
+            .instance_id("test-sharer")
+            .subtype("filesharing")
+            .build()).unwrap();
+        let chatter = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-chatter")
+            .instance_id("test-chatter")
+            .subtype("chat")
+            .build()).unwrap();
+        let browser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-subtype-browser")
+            .instance_id("test-subtype-browser")
+            .subtype("filesharing")
+            .build()).unwrap();
+
+        browser.start().await.unwrap();
+        sharer.start().await.unwrap();
+        chatter.start().await.unwrap();
+
+        let peers = browser.discover_peers(Some(Duration::from_secs(3))).await.unwrap();
+        assert!(peers.iter().any(|peer| peer.id == "test-sharer"));
+        assert!(!peers.iter().any(|peer| peer.id == "test-chatter"));
+
+        chatter.stop().await.unwrap();
+        sharer.stop().await.unwrap();
+        browser.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_by_service_name() {
+        let advertiser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-resolve")
+            .instance_id("test-resolve")
+            .build()).unwrap();
+        let resolver = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-resolver")
+            .instance_id("test-resolver")
+            .build()).unwrap();
+
+        advertiser.start().await.unwrap();
+
+        let peer = resolver.resolve("qopyapp-test-resolve", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(peer.id, "test-resolve");
+
+        assert!(matches!(
+            resolver.resolve("qopyapp-test-missing", Duration::from_millis(500)).await,
+            Err(PeerDiscoveryError::DiscoveryTimeout(_))
+        ));
+
+        resolver.stop().await.unwrap();
+        advertiser.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_browse_retries_after_daemon_failure() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-retry")
+            .instance_id("test-retry")
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // Ends the running browse, and every retry fails until the daemon is back
+        discovery.transport().unwrap().shutdown().await.unwrap();
+
+        let retried = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut failures = 0;
+            while failures < 2 {
+                if let Ok(PeerEvent::Error(PeerDiscoveryError::ServiceDiscoveryFailed(_))) = receiver.recv().await {
+                    failures += 1;
+                }
+            }
+        }).await;
+        assert!(retried.is_ok(), "browse task gave up after the first failure");
+
+        discovery.stop().await.unwrap();
+    }
+
+    fn loopback_discovery(instance_id: &str) -> PeerDiscovery {
+        PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name(format!("qopyapp-{}", instance_id))
+            .instance_id(instance_id)
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .test_mode(true)
+            .build()).unwrap()
+    }
+
+    fn loopback_peer(port: u16, version: &str) -> ServiceInfo {
+        ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            "loopback-peer",
+            "loopback-peer.local.",
+            "192.168.1.20",
+            port,
+            &[(INSTANCE_ID_PROPERTY, "loopback-peer"), ("version", version)][..],
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_loopback_resolve() {
+        let backend = Arc::new(crate::StaticBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-loopback-resolve")
+            .instance_id("test-loopback-resolve")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        assert!(backend.registered().contains(&discovery.local_fullname()));
+
+        discovery.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(9000, "1"))).unwrap();
+
+        match receiver.recv().await.unwrap() {
+            PeerEvent::PeerDiscovered(peer) => {
+                assert_eq!(peer.id, "loopback-peer");
+                assert_eq!(peer.port, 9000);
+            }
+            event => panic!("expected PeerDiscovered, got {:?}", event),
+        }
+        assert_eq!(discovery.peer_count().await, 1);
+
+        discovery.stop().await.unwrap();
+        assert!(!backend.registered().contains(&discovery.local_fullname()));
+    }
+
+    #[tokio::test]
+    async fn test_loopback_update() {
+        let discovery = loopback_discovery("test-loopback-update");
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for version in ["1", "1", "2"] {
+            discovery.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(9000, version))).unwrap();
+        }
+
+        // The identical re-resolve is only a refresh
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(_)));
+        match receiver.recv().await.unwrap() {
+            PeerEvent::PeerUpdated(peer) => assert_eq!(peer.properties["version"], "2"),
+            event => panic!("expected PeerUpdated, got {:?}", event),
+        }
+        assert_eq!(discovery.peer_count().await, 1);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_loopback_remove() {
+        let discovery = loopback_discovery("test-loopback-remove");
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        let info = loopback_peer(9000, "1");
+        let fullname = info.get_fullname().to_string();
+        discovery.inject_service_event(ServiceEvent::ServiceResolved(info)).unwrap();
+        discovery.inject_service_event(ServiceEvent::ServiceRemoved("_qopyapp._tcp.local.".to_string(), fullname)).unwrap();
+
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(_)));
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerLost(peer) if peer.id == "loopback-peer"));
+        assert_eq!(discovery.peer_count().await, 0);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_inject_requires_test_mode() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let event = ServiceEvent::ServiceResolved(loopback_peer(9000, "1"));
+        assert!(discovery.inject_service_event(event).is_err());
+
+        // Nothing is browsing before start()
+        let discovery = loopback_discovery("test-loopback-idle");
+        let event = ServiceEvent::ServiceResolved(loopback_peer(9000, "1"));
+        assert!(discovery.inject_service_event(event).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_discover_peers_detailed() {
+        let discovery = loopback_discovery("test-detailed");
+        let injector = discovery.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            injector.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(9000, "1"))).unwrap();
+        });
+
+        let result = discovery.discover_peers_detailed(Some(Duration::from_millis(500))).await.unwrap();
+        assert_eq!(result.peers.len(), 1);
+        assert!(result.timed_out);
+        assert!(result.elapsed >= Duration::from_millis(500));
+        // ServiceStarted and PeerDiscovered
+        assert!(result.events_seen >= 2);
+        discovery.stop().await.unwrap();
+
+        // An early exit doesn't count as timing out
+        let discovery = PeerDiscovery::new(DiscoveryConfig {
+            early_exit_after: Some(1),
+            ..discovery.config.clone()
+        }).unwrap();
+        let injector = discovery.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            injector.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(9000, "1"))).unwrap();
+        });
+
+        let result = discovery.discover_peers_detailed(Some(Duration::from_secs(5))).await.unwrap();
+        assert_eq!(result.peers.len(), 1);
+        assert!(!result.timed_out);
+        assert!(result.elapsed < Duration::from_secs(5));
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_multicast_interfaces() {
+        let result = PeerDiscovery::new(DiscoveryConfig::builder()
+            .multicast_interface("203.0.113.77".parse().unwrap())
+            .build());
+        assert!(matches!(result, Err(PeerDiscoveryError::NetworkInterfaceError(_))));
+
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .multicast_interface(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .build());
+        assert!(discovery.is_ok());
+    }
+
+    #[test]
+    fn test_daemon_init_failure() {
+        let result = crate::transport::start_daemon(|| Err(mdns_sd::Error::Msg("no multicast socket".to_string())));
+        assert!(matches!(result, Err(PeerDiscoveryError::DaemonInitFailed(msg)) if msg.contains("no multicast socket")));
+    }
+
+    #[tokio::test]
+    async fn test_stop_shuts_down_daemon() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-cycles")
+            .instance_id("test-cycles")
+            .build()).unwrap();
+
+        for _ in 0..3 {
+            discovery.start().await.unwrap();
+            assert!(discovery.transport().is_ok());
+
+            // Neither the daemon nor any background task survives until the next start()
+            discovery.stop().await.unwrap();
+            assert!(discovery.transport().is_err());
+            assert!(discovery.tasks.lock().unwrap().is_empty());
+        }
+
+        // Clones share the daemon, so starting one brings it back for all
+        let clone = discovery.clone();
+        clone.start().await.unwrap();
+        assert!(discovery.transport().is_ok());
+        assert!(discovery.is_running().await);
+        discovery.stop().await.unwrap();
+        assert!(clone.transport().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_progress_events() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig {
+            scan_progress_interval: Some(Duration::from_millis(500)),
+            ..loopback_discovery("test-progress").config.clone()
+        }).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        let result = discovery.discover_peers_detailed(Some(Duration::from_secs(2))).await.unwrap();
+
+        let mut progress = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let PeerEvent::ScanProgress { elapsed, peers_found } = event {
+                progress.push((elapsed, peers_found));
+            }
+        }
+        assert!(!progress.is_empty());
+        assert!(progress.iter().all(|(elapsed, peers_found)| *elapsed <= result.elapsed && *peers_found == 0));
+        // Progress reports don't count as events the scan saw
+        assert_eq!(result.events_seen, 1);
+
+        discovery.stop().await.unwrap();
+
+        // Without the interval a scan stays quiet
+        let discovery = loopback_discovery("test-no-progress");
+        let mut receiver = discovery.subscribe();
+        discovery.discover_peers(Some(Duration::from_secs(1))).await.unwrap();
+        while let Ok(event) = receiver.try_recv() {
+            assert!(!matches!(event, PeerEvent::ScanProgress { .. }));
+        }
+        discovery.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("laptop", "Laptop"));
+        assert!(!wildcard_match("laptop", "laptop-2"));
+        assert!(wildcard_match("office-*", "Office-Printer"));
+        assert!(wildcard_match("*-tv", "living-room-tv"));
+        assert!(wildcard_match("a*b*c", "a-b-c"));
+        assert!(!wildcard_match("a*b*c", "a-c-b"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match("*", ""));
+    }
+
+    fn filtered_peer(name: &str, device_type: &str) -> ServiceInfo {
+        ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            name,
+            &format!("{}.local.", name),
+            "192.168.1.40",
+            9000,
+            &[(INSTANCE_ID_PROPERTY, name), (DEVICE_TYPE_PROPERTY, device_type)][..],
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_peer_filter_allowlist() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .peer_filter(PeerFilter::default()
+                .allow_name("office-*")
+                .allow_device_type(DeviceType::Desktop))
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for (name, device_type) in [("office-pc", "desktop"), ("office-phone", "phone"), ("home-pc", "desktop")] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer(name, device_type))).await.unwrap();
+        }
+
+        let ids: Vec<String> = discovery.get_peers().await.into_iter().map(|peer| peer.id).collect();
+        assert_eq!(ids, vec!["office-pc"]);
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.id == "office-pc"));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_filter_blocklist() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .peer_filter(PeerFilter::default().deny_name("noisy-tv"))
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for name in ["laptop", "noisy-tv", "NOISY-TV"] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer(name, "laptop"))).await.unwrap();
+        }
+
+        let ids: Vec<String> = discovery.get_peers().await.into_iter().map(|peer| peer.id).collect();
+        assert_eq!(ids, vec!["laptop"]);
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.id == "laptop"));
+        assert!(receiver.try_recv().is_err());
+
+        // A peer whose advertisement stops passing the filter is dropped
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .peer_filter(PeerFilter::default().deny_device_type(DeviceType::Server))
+            .build()).unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("box", "desktop"))).await.unwrap();
+        assert_eq!(discovery.peer_count().await, 1);
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("box", "server"))).await.unwrap();
+        assert_eq!(discovery.peer_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_without_usable_address_is_pending() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .ip_preference(IpPreference::Ipv4Only)
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for ip in ["2001:db8::7", "2001:db8::7,192.168.1.50"] {
+            let info = ServiceInfo::new(
+                "_qopyapp._tcp.local.",
+                "v6-peer",
+                "v6-peer.local.",
+                ip,
+                9000,
+                &[(INSTANCE_ID_PROPERTY, "v6-peer")][..],
+            ).unwrap();
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+        }
+
+        // Reported right away, just without anywhere to connect to
+        match receiver.recv().await.unwrap() {
+            PeerEvent::PeerDiscovered(peer) => {
+                assert!(peer.address_pending);
+                assert!(peer.addresses.is_empty());
+                assert!(peer.socket_addrs().is_empty());
+                assert_eq!(peer.hostname, "v6-peer.local.");
+            }
+            event => panic!("expected PeerDiscovered, got {:?}", event),
+        }
+
+        // The address arriving later completes it
+        match receiver.recv().await.unwrap() {
+            PeerEvent::PeerUpdated(peer) => {
+                assert!(!peer.address_pending);
+                assert_eq!(peer.ip, "192.168.1.50".parse::<IpAddr>().unwrap());
+            }
+            event => panic!("expected PeerUpdated, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_stops_discovery() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig {
+            idle_timeout: Some(Duration::from_millis(300)),
+            ..loopback_discovery("test-idle").config.clone()
+        }).unwrap();
+        let mut receiver = discovery.subscribe();
+        discovery.start().await.unwrap();
+
+        // New peers keep it alive well past the timeout
+        for port in 9000..9005 {
+            sleep(Duration::from_millis(150)).await;
+            discovery.inject_service_event(ServiceEvent::ServiceResolved(loopback_peer(port, "1"))).unwrap();
+        }
+        assert!(discovery.is_running().await);
+
+        // Then nothing happens and it stops itself
+        let stopped = tokio::time::timeout(Duration::from_secs(2), async {
+            while !matches!(receiver.recv().await, Ok(PeerEvent::ServiceStopped)) {}
+        }).await;
+        assert!(stopped.is_ok());
+        assert!(!discovery.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_with_peers() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        {
+            let mut peers = discovery.discovered_peers.write().await;
+            for (name, ip) in [("one", "192.168.1.10"), ("two", "192.168.1.11"), ("three", "10.0.0.5")] {
+                peers.insert(name.to_string(), test_peer(name, ip));
+            }
+        }
+
+        let local_count = discovery.with_peers(|peers| {
+            peers.values().filter(|peer| peer.ip.to_string().starts_with("192.168.")).count()
+        }).await;
+        assert_eq!(local_count, 2);
+
+        let mut names: Vec<&'static str> = discovery.with_peers(|peers| {
+            ["one", "two", "three", "four"].into_iter().filter(|name| peers.contains_key(*name)).collect()
+        }).await;
+        names.sort();
+        assert_eq!(names, vec!["one", "three", "two"]);
+    }
+
+    #[test]
+    fn test_version_at_least() {
+        assert!(version_at_least("1.2.0", "1.2"));
+        assert!(version_at_least("v2", "1.9.9"));
+        assert!(version_at_least("1.10", "1.9"));
+        assert!(!version_at_least("1.1.9", "1.2.0"));
+        assert!(!version_at_least("beta", "1.0"));
+    }
+
+    fn versioned_peer(name: &str, version: Option<&str>) -> ServiceInfo {
+        let mut properties = vec![(INSTANCE_ID_PROPERTY, name)];
+        if let Some(version) = version {
+            properties.push((VERSION_PROPERTY, version));
+        }
+        ServiceInfo::new(
+            "_qopyapp._tcp.local.",
+            name,
+            &format!("{}.local.", name),
+            "192.168.1.60",
+            9000,
+            &properties[..],
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_peer_version_compatibility() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .min_compatible_version("1.2")
+            .build()).unwrap();
+
+        for (name, version) in [("current", Some("1.2.0")), ("newer", Some("2.0")), ("older", Some("1.1.5")), ("unversioned", None)] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(versioned_peer(name, version))).await.unwrap();
+        }
+
+        for (id, expected) in [("current", true), ("newer", true), ("older", false), ("unversioned", false)] {
+            assert_eq!(discovery.get_peer_by_id(id).await.unwrap().compatible, expected, "{}", id);
+        }
+
+        // Without a minimum every peer is compatible
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(versioned_peer("older", Some("0.1")))).await.unwrap();
+        assert!(discovery.get_peer_by_id("older").await.unwrap().compatible);
+    }
+
+    #[tokio::test]
+    async fn test_drop_incompatible_peers() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .min_compatible_version("1.2")
+            .drop_incompatible_peers(true)
+            .build()).unwrap();
+
+        for (name, version) in [("current", "1.2.0"), ("older", "1.1.5")] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(versioned_peer(name, Some(version)))).await.unwrap();
+        }
+
+        let ids: Vec<String> = discovery.get_peers().await.into_iter().map(|peer| peer.id).collect();
+        assert_eq!(ids, vec!["current"]);
+    }
+
+    #[tokio::test]
+    async fn test_static_backend() {
+        let other_type = ServiceInfo::new(
+            "_other._tcp.local.",
+            "static-other",
+            "static-other.local.",
+            "192.168.1.41",
+            9000,
+            &[(INSTANCE_ID_PROPERTY, "static-other")][..],
+        ).unwrap();
+        let backend = Arc::new(crate::StaticBackend::new(vec![
+            filtered_peer("static-one", "desktop"),
+            filtered_peer("static-two", "phone"),
+            other_type,
+        ]));
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-static")
+            .instance_id("test-static")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .build()).unwrap();
+
+        let mut ids: Vec<String> = discovery.discover_peers_strict(2, Some(Duration::from_secs(2))).await.unwrap()
+            .into_iter()
+            .map(|peer| peer.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["static-one", "static-two"]);
+        assert_eq!(backend.registered(), vec![discovery.local_fullname()]);
+
+        discovery.stop().await.unwrap();
+        assert!(backend.registered().is_empty());
+
+        // The backend outlives stop(), so starting again browses it again
+        discovery.start().await.unwrap();
+        discovery.wait_for_peer(|peer| peer.id == "static-two", Duration::from_secs(2)).await.unwrap();
+        assert_eq!(discovery.peer_count().await, 2);
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_manual_peers() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("found", "laptop"))).await.unwrap();
+        discovery.add_manual_peer(test_peer("typed-in", "10.0.0.9")).await;
+
+        let mut peers = discovery.get_peers_sorted(PeerSort::ByName).await;
+        assert_eq!(peers.len(), 2);
+        let manual = peers.pop().unwrap();
+        assert_eq!((manual.name.as_str(), manual.source), ("typed-in", PeerSource::Manual));
+        assert_eq!(peers[0].source, PeerSource::Mdns);
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.source == PeerSource::Mdns));
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.source == PeerSource::Manual));
+
+        // Pruning leaves manual peers alone
+        sleep(Duration::from_millis(20)).await;
+        let pruned = discovery.prune_stale_peers(Duration::from_millis(10)).await;
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(discovery.peer_count().await, 1);
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerLost(peer) if peer.id == "found"));
+
+        // Only manual peers can be removed by hand
+        assert!(discovery.remove_manual_peer("found._qopyapp._tcp.local.").await.is_none());
+        assert!(discovery.remove_manual_peer("typed-in").await.is_some());
+        assert!(discovery.get_peers().await.is_empty());
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerLost(peer) if peer.name == "typed-in"));
+    }
+
+    #[test]
+    fn test_allowed_device_types() {
+        let config = |device_type: &str| DiscoveryConfig::builder()
+            .test_mode(true)
+            .property(DEVICE_TYPE_PROPERTY, device_type)
+            .allowed_device_types(["laptop", "phone"])
+            .build();
+
+        assert!(PeerDiscovery::new(config("laptop")).is_ok());
+        let Err(err) = PeerDiscovery::new(config("laptp")) else {
+            panic!("a misspelled device type should be rejected");
+        };
+        assert!(matches!(err, PeerDiscoveryError::InvalidDeviceType(msg) if msg.contains("laptp")));
+
+        // The profile's device type is checked when no property overrides it
+        let profile = DeviceProfile::from_properties(&DiscoveryConfig::default().with_device_type(DeviceType::Tablet).properties);
+        let config = DiscoveryConfig::builder()
+            .test_mode(true)
+            .profile(profile)
+            .allowed_device_types(["laptop"])
+            .build();
+        assert!(matches!(PeerDiscovery::new(config), Err(PeerDiscoveryError::InvalidDeviceType(_))));
+
+        // No restriction, or nothing advertised, is always fine
+        let config = DiscoveryConfig::builder().test_mode(true).property(DEVICE_TYPE_PROPERTY, "laptp").build();
+        assert!(PeerDiscovery::new(config).is_ok());
+        let config = DiscoveryConfig::builder().test_mode(true).allowed_device_types(["laptop"]).build();
+        assert!(PeerDiscovery::new(config).is_ok());
+    }
+
+    #[test]
+    fn test_config_toml_round_trip() {
+        let config = DiscoveryConfig::builder()
+            .service_name("toml-device")
+            .port(9100)
+            .property("room", "office")
+            .discovery_timeout(Duration::from_millis(2500))
+            .peer_ttl(None)
+            .query_interval(Some(Duration::from_secs(5)))
+            .peer_filter(PeerFilter::default().allow_name("office-*").deny_device_type(DeviceType::Phone))
+            .allowed_device_types(["laptop"])
+            .build()
+            .with_device_type(DeviceType::Laptop);
+
+        let toml = config.to_toml_string().unwrap();
+        assert!(toml.contains("discovery_timeout = 2.5"));
+        let parsed = DiscoveryConfig::from_toml_str(&toml).unwrap();
+
+        assert_eq!(parsed.service_name, "toml-device");
+        assert_eq!(parsed.port, 9100);
+        assert_eq!(parsed.properties, config.properties);
+        assert_eq!(parsed.discovery_timeout, Duration::from_millis(2500));
+        assert_eq!(parsed.peer_ttl, None);
+        assert_eq!(parsed.query_interval, Some(Duration::from_secs(5)));
+        assert_eq!(parsed.peer_filter, config.peer_filter);
+        assert_eq!(parsed.allowed_device_types, config.allowed_device_types);
+        assert_eq!(parsed.instance_id, config.instance_id);
+
+        // Missing fields fall back to the defaults
+        let partial = DiscoveryConfig::from_toml_str("port = 7000\nannounce_interval = 60").unwrap();
+        assert_eq!(partial.port, 7000);
+        assert_eq!(partial.announce_interval, Duration::from_secs(60));
+        assert_eq!(partial.service_type, DiscoveryConfig::default().service_type);
+        assert_eq!(partial.query_interval, DiscoveryConfig::default().query_interval);
+
+        // Turning the default re-query off survives a round trip
+        let quiet = DiscoveryConfig::builder().query_interval(None).build();
+        assert_eq!(DiscoveryConfig::from_toml_str(&quiet.to_toml_string().unwrap()).unwrap().query_interval, None);
+
+        assert!(matches!(
+            DiscoveryConfig::from_toml_str("port = \"not a port\""),
+            Err(PeerDiscoveryError::SerializationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update_debounce() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .update_debounce(Some(Duration::from_millis(300)))
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for version in ["1", "2", "3", "4", "5"] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(versioned_peer("chatty", Some(version)))).await.unwrap();
+        }
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(_)));
+
+        // The first change goes out right away, the rest wait for the window to close
+        match receiver.recv().await.unwrap() {
+            PeerEvent::PeerUpdated(peer) => assert_eq!(peer.properties["version"], "2"),
+            event => panic!("expected PeerUpdated, got {:?}", event),
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await.is_err());
+
+        match tokio::time::timeout(Duration::from_millis(500), receiver.recv()).await.unwrap().unwrap() {
+            PeerEvent::PeerUpdated(peer) => assert_eq!(peer.properties["version"], "5"),
+            event => panic!("expected PeerUpdated, got {:?}", event),
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(400), receiver.recv()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_window_closes_with_peer() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .update_debounce(Some(Duration::from_secs(60)))
+            .build()).unwrap();
+
+        for name in ["pruned", "cleared"] {
+            for version in ["1", "2"] {
+                discovery.handle_service_event(ServiceEvent::ServiceResolved(versioned_peer(name, Some(version)))).await.unwrap();
+            }
+        }
+        assert_eq!(discovery.update_windows.lock().unwrap().len(), 2);
+
+        // Pruning drops the window along with the peer
+        discovery.discovered_peers.write().await.get_mut("pruned").unwrap().last_seen = SystemTime::UNIX_EPOCH;
+        assert_eq!(discovery.prune_stale_peers(Duration::from_secs(60)).await.len(), 1);
+        assert!(!discovery.update_windows.lock().unwrap().contains_key("pruned"));
+
+        discovery.clear_peers(false).await;
+        assert!(discovery.update_windows.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_serve_liveness() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-liveness-server")
+            .test_mode(true)
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .port(port)
+            .build()).unwrap();
+        let client = loopback_discovery("liveness-client");
+
+        server.start().await.unwrap();
+        let _listener = server.serve_liveness().await.unwrap();
+
+        let mut peer = test_peer("liveness-server", "127.0.0.1");
+        peer.port = port;
+        assert!(client.verify_peer(&peer).await);
+
+        let mut stream = client.connect_to(&peer).await.unwrap();
+        let mut response = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut response).await.unwrap();
+        assert_eq!(response, LIVENESS_RESPONSE);
+
+        // stop() takes the listener down with the other background tasks
+        server.stop().await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        assert!(!client.verify_peer(&peer).await);
+    }
+
+    #[test]
+    fn test_binary_txt_values() {
+        // mdns-sd 0.12 can't build a ServiceInfo with non-UTF-8 values, so test the conversion itself
+        let blob: &[u8] = &[0xff, 0xfe, 0x00, 0x80];
+        let raw = raw_txt_properties([
+            (INSTANCE_ID_PROPERTY, Some("binary-peer".as_bytes())),
+            ("key", Some(blob)),
+            ("flag", None),
+        ]);
+
+        assert_eq!(raw["key"], blob);
+        assert_eq!(raw[INSTANCE_ID_PROPERTY], b"binary-peer");
+        assert!(!raw.contains_key("flag"));
+
+        // The string map still has the key, just not the exact bytes
+        let properties = decode_txt_properties(&raw);
+        assert_eq!(properties["key"], String::from_utf8_lossy(blob));
+        assert_eq!(properties[INSTANCE_ID_PROPERTY], "binary-peer");
+    }
+
+    #[tokio::test]
+    async fn test_peer_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DiscoveryConfig::builder()
+            .service_name("qopyapp-test-cache")
+            .instance_id("test-cache")
+            .test_mode(true)
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .cache_path(dir.path().join("peers.json"))
+            .build();
+
+        let first = PeerDiscovery::new(config.clone()).unwrap();
+        first.start().await.unwrap();
+        first.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("cached", "laptop"))).await.unwrap();
+        first.add_manual_peer(test_peer("typed-in", "10.0.0.9")).await;
+        first.stop().await.unwrap();
+
+        // The next run starts out with the saved peer, marked as unconfirmed
+        let second = PeerDiscovery::new(config).unwrap();
+        second.start().await.unwrap();
+        let peers = second.get_peers().await;
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].id, "cached");
+        assert_eq!(peers[0].source, PeerSource::Cached);
+        assert_eq!(peers[0].device_type(), DeviceType::Laptop);
+
+        // Seeing it again confirms it
+        second.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("cached", "laptop"))).await.unwrap();
+        assert_eq!(second.get_peer_by_id("cached").await.unwrap().source, PeerSource::Mdns);
+        second.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_cache_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json");
+
+        let mut old = test_peer("old", "192.168.1.50");
+        old.last_seen = SystemTime::now() - Duration::from_secs(3 * 60 * 60);
+        let recent = test_peer("recent", "192.168.1.51");
+        std::fs::write(&path, serde_json::to_vec(&vec![old, recent]).unwrap()).unwrap();
+
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .cache_path(&path)
+            .cache_max_age(Duration::from_secs(60 * 60))
+            .build()).unwrap();
         let mut receiver = discovery.subscribe();
-        
-        // Start the service
+
+        assert_eq!(discovery.load_peer_cache().await.unwrap(), 1);
+        let peer = discovery.get_peer_by_id("recent").await.unwrap();
+        assert_eq!(peer.source, PeerSource::Cached);
+        assert!(discovery.get_peer_by_id("old").await.is_none());
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(peer) if peer.id == "recent"));
+
+        // Pruning holds cached peers to the cache age, not the peer TTL
+        assert!(discovery.prune_stale_peers(Duration::ZERO).await.is_empty());
+
+        // No cache file yet is not an error
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(discovery.load_peer_cache().await.unwrap(), 0);
+    }
+
+    /// Backend whose browses always fail, registration works
+    #[derive(Debug)]
+    struct FailingBrowseBackend;
+
+    impl DiscoveryBackend for FailingBrowseBackend {
+        fn register(&self, _info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+            Ok(())
+        }
+
+        fn unregister(&self, _fullname: &str) -> Result<(), PeerDiscoveryError> {
+            Ok(())
+        }
+
+        fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+            Err(PeerDiscoveryError::ServiceDiscoveryFailed(format!("cannot browse {}", service_type)))
+        }
+
+        fn stop_browse(&self, _service_type: &str) -> Result<(), PeerDiscoveryError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_reports_browse_failure() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-browse-failure")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(Arc::new(FailingBrowseBackend))
+            .build()).unwrap();
+
+        let err = discovery.start().await.unwrap_err();
+        assert!(matches!(err, PeerDiscoveryError::ServiceDiscoveryFailed(msg) if msg.contains("_qopyapp._tcp.local.")));
+        assert!(!discovery.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_browse_all_service_types() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-meta-query")
+            .instance_id("test-meta-query")
+            .build()).unwrap();
         discovery.start().await.unwrap();
-        
-        // Should receive ServiceStarted event
-        let event = receiver.recv().await.unwrap();
-        assert!(matches!(event, PeerEvent::ServiceStarted));
-        
+
+        let service_types = discovery.browse_all_service_types(Duration::from_secs(3)).await.unwrap();
+        assert!(service_types.contains(&"_qopyapp._tcp.local.".to_string()), "got {:?}", service_types);
+
         discovery.stop().await.unwrap();
+        assert!(discovery.browse_all_service_types(Duration::from_millis(10)).await.is_err());
+    }
+
+    /// `StaticBackend` on a host whose interfaces can't multicast
+    #[derive(Debug, Default)]
+    struct NoMulticastBackend {
+        inner: crate::StaticBackend,
+    }
+
+    impl DiscoveryBackend for NoMulticastBackend {
+        fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+            self.inner.register(info)
+        }
+
+        fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.unregister(fullname)
+        }
+
+        fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+            self.inner.browse(service_type)
+        }
+
+        fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.stop_browse(service_type)
+        }
+
+        fn multicast_available(&self) -> bool {
+            false
+        }
     }
 
     #[tokio::test]
-    async fn test_peer_discovery_get_peers() {
-        let config = DiscoveryConfig::default();
+    async fn test_manual_only_without_multicast() {
+        let interface = |is_up: bool, supports_multicast: bool| NetworkInterface {
+            name: "wlan0".to_string(),
+            ip: "192.168.1.10".parse().unwrap(),
+            is_loopback: false,
+            is_up,
+            supports_multicast,
+        };
+        assert!(multicast_available(&[interface(false, true), interface(true, true)]));
+        assert!(!multicast_available(&[interface(false, true), interface(true, false)]));
+        assert!(!multicast_available(&[]));
+
+        let backend = Arc::new(NoMulticastBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-no-multicast")
+            .instance_id("no-multicast")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        discovery.start().await.unwrap();
+        assert!(discovery.is_running().await);
+        assert!(discovery.is_manual_only().await);
+        assert!(backend.inner.registered().is_empty());
+        match receiver.recv().await.unwrap() {
+            PeerEvent::Error(PeerDiscoveryError::NetworkInterfaceError(msg)) => assert!(msg.contains("multicast unavailable")),
+            event => panic!("expected a multicast error, got {:?}", event),
+        }
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::ServiceStarted));
+
+        // Manual peers still work
+        discovery.add_manual_peer(test_peer("typed-in", "10.0.0.9")).await;
+        assert_eq!(discovery.peer_count().await, 1);
+
+        discovery.stop().await.unwrap();
+        assert!(!discovery.is_manual_only().await);
+    }
+
+    /// `StaticBackend` that counts registrations and shutdowns
+    #[derive(Debug, Default)]
+    struct CountingBackend {
+        inner: crate::StaticBackend,
+        registrations: AtomicU64,
+        shutdowns: AtomicU64,
+    }
+
+    #[async_trait::async_trait]
+    impl DiscoveryBackend for CountingBackend {
+        fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+            self.registrations.fetch_add(1, Ordering::SeqCst);
+            self.inner.register(info)
+        }
+
+        fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.unregister(fullname)
+        }
+
+        fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+            self.inner.browse(service_type)
+        }
+
+        fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.stop_browse(service_type)
+        }
+
+        async fn shutdown(&self) -> Result<(), PeerDiscoveryError> {
+            self.shutdowns.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restart_grace() {
+        let backend = Arc::new(CountingBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-restart-grace")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .restart_grace(Some(Duration::from_millis(300)))
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+
+        // A quick stop and start keeps the registration and stays quiet
+        discovery.stop().await.unwrap();
+        assert!(!discovery.is_running().await);
+        discovery.start().await.unwrap();
+        assert!(discovery.is_running().await);
+        assert_eq!(backend.registrations.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.inner.registered().len(), 1);
+        sleep(Duration::from_millis(400)).await;
+        assert!(matches!(receiver.try_recv(), Err(broadcast::error::TryRecvError::Empty)));
+
+        // Past the grace period the registration goes away for real
+        discovery.stop().await.unwrap();
+        assert_eq!(backend.inner.registered().len(), 1);
+        sleep(Duration::from_millis(400)).await;
+        assert!(backend.inner.registered().is_empty());
+        assert!(matches!(receiver.recv().await.unwrap(), PeerEvent::ServiceStopped));
+
+        discovery.start().await.unwrap();
+        assert_eq!(backend.registrations.load(Ordering::SeqCst), 2);
+        discovery.restart().await.unwrap();
+        assert_eq!(backend.registrations.load(Ordering::SeqCst), 3);
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pruning_spares_peers_that_answer() {
+        // A re-browse of the static backend resolves its listed service again, like a peer answering a query
+        let backend = Arc::new(crate::StaticBackend::new(vec![loopback_peer(9000, "1")]));
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-prune-requery")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend)
+            .peer_ttl(Some(Duration::from_secs(1)))
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+        let mut receiver = discovery.subscribe();
+        discovery.inject_service_event(ServiceEvent::ServiceResolved(versioned_peer("silent", Some("1")))).unwrap();
+
+        let lost = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let PeerEvent::PeerLost(peer) = receiver.recv().await.unwrap() {
+                    return peer;
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(lost.id, "silent");
+        let ids: Vec<String> = discovery.get_peers().await.into_iter().map(|peer| peer.id).collect();
+        assert_eq!(ids, vec!["loopback-peer".to_string()]);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_zero_announce_interval_is_clamped() {
+        let backend = Arc::new(CountingBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-zero-announce")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .announce_interval(Duration::ZERO)
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+
+        // One registration on start and at most one re-announce, not a tight loop
+        sleep(MIN_ANNOUNCE_INTERVAL + Duration::from_millis(500)).await;
+        assert!(backend.registrations.load(Ordering::SeqCst) <= 2);
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peers_by_device_type() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+        for (name, device_type) in [("b-laptop", Some("laptop")), ("a-laptop", Some("laptop")), ("phone", Some("phone")), ("mystery", None)] {
+            let mut peer = test_peer(name, "192.168.1.10");
+            if let Some(device_type) = device_type {
+                peer.properties.insert(DEVICE_TYPE_PROPERTY.to_string(), device_type.to_string());
+            }
+            discovery.discovered_peers.write().await.insert(peer.id.clone(), peer);
+        }
+
+        let groups = discovery.peers_by_device_type().await;
+        assert_eq!(groups.len(), 3);
+        let names = |device_type: &str| groups[device_type].iter().map(|peer| peer.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names("laptop"), vec!["a-laptop", "b-laptop"]);
+        assert_eq!(names("phone"), vec!["phone"]);
+        assert_eq!(names("unknown"), vec!["mystery"]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready() {
+        let discovery = loopback_discovery("wait-ready");
+        assert!(matches!(
+            discovery.wait_until_ready(Duration::from_millis(50)).await,
+            Err(PeerDiscoveryError::DiscoveryTimeout(_))
+        ));
+
+        // A waiter that starts before the service is up is woken by ServiceStarted
+        let waiter = discovery.clone();
+        let waiting = tokio::spawn(async move { waiter.wait_until_ready(Duration::from_secs(2)).await });
+        sleep(Duration::from_millis(20)).await;
+        discovery.start().await.unwrap();
+        waiting.await.unwrap().unwrap();
+
+        discovery.wait_until_ready(Duration::from_millis(50)).await.unwrap();
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_peers_evicts_oldest() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder().max_peers(2).build()).unwrap();
+        let mut receiver = discovery.subscribe();
+
+        for name in ["first", "second"] {
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer(name, "laptop"))).await.unwrap();
+            sleep(Duration::from_millis(10)).await;
+        }
+        // Hearing from "first" again makes "second" the least recently seen
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("first", "laptop"))).await.unwrap();
+        discovery.handle_service_event(ServiceEvent::ServiceResolved(filtered_peer("third", "laptop"))).await.unwrap();
+
+        assert_eq!(discovery.peer_count().await, 2);
+        assert!(discovery.get_peer_by_id("second").await.is_none());
+        assert!(discovery.get_peer_by_id("first").await.is_some());
+        assert!(discovery.get_peer_by_id("third").await.is_some());
+
+        let events: Vec<PeerEvent> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        let lost: Vec<&str> = events.iter()
+            .filter_map(|event| match event {
+                PeerEvent::PeerLost(peer) => Some(peer.id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lost, vec!["second"]);
+    }
+
+    #[test]
+    fn test_diff_peers_added() {
+        let laptop = test_peer("laptop", "192.168.1.10");
+        let phone = test_peer("phone", "192.168.1.11");
+
+        let diff = diff_peers(std::slice::from_ref(&laptop), &[laptop.clone(), phone.clone()]);
+        assert_eq!(diff.added, vec![phone]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+        assert!(diff_peers(std::slice::from_ref(&laptop), std::slice::from_ref(&laptop)).is_empty());
+    }
+
+    #[test]
+    fn test_diff_peers_removed() {
+        let laptop = test_peer("laptop", "192.168.1.10");
+        let phone = test_peer("phone", "192.168.1.11");
+
+        let diff = diff_peers(&[laptop.clone(), phone.clone()], &[phone]);
+        assert_eq!(diff.removed, vec![laptop]);
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_peers_changed() {
+        let before = test_peer("laptop", "192.168.1.10");
+        let mut after = before.clone();
+        after.properties.insert(VERSION_PROPERTY.to_string(), "2.0.0".to_string());
+        // Being seen again isn't a change by itself
+        let mut seen_again = before.clone();
+        seen_again.last_seen += Duration::from_secs(5);
+
+        let diff = diff_peers(std::slice::from_ref(&before), std::slice::from_ref(&after));
+        assert_eq!(diff.changed, vec![after]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(diff_peers(&[before], &[seen_again]).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_fullname() {
+        assert_eq!(normalize_fullname("Laptop._qopyapp._tcp.local."), "laptop._qopyapp._tcp.local.");
+        assert_eq!(normalize_fullname("laptop._qopyapp._tcp.local"), "laptop._qopyapp._tcp.local.");
+        assert_eq!(normalize_fullname("laptop._qopyapp._tcp.local.."), "laptop._qopyapp._tcp.local.");
+    }
+
+    #[tokio::test]
+    async fn test_removal_with_unnormalized_fullname() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::default()).unwrap();
+
+        for (name, removed_as) in [("dotted", "dotted._qopyapp._tcp.local"), ("Cased", "cased._qopyapp._tcp.local.")] {
+            let info = ServiceInfo::new(
+                "_qopyapp._tcp.local.",
+                name,
+                &format!("{}.local.", name),
+                "192.168.1.80",
+                9000,
+                None,
+            ).unwrap();
+            discovery.handle_service_event(ServiceEvent::ServiceResolved(info)).await.unwrap();
+            assert_eq!(discovery.peer_count().await, 1);
+
+            discovery.handle_service_event(ServiceEvent::ServiceRemoved(
+                "_qopyapp._tcp.local.".to_string(),
+                removed_as.to_string(),
+            )).await.unwrap();
+            assert_eq!(discovery.peer_count().await, 0, "{} was not removed as {}", name, removed_as);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_interval() {
+        let config = DiscoveryConfig::builder()
+            .service_name("qopyapp-test-requery")
+            .instance_id("test-requery")
+            .query_interval(Some(Duration::from_millis(200)))
+            .build();
+        assert_eq!(config.query_interval, Some(Duration::from_millis(200)));
+
         let discovery = PeerDiscovery::new(config).unwrap();
-        
-        // Initially no peers
-        let peers = discovery.get_peers().await;
-        assert!(peers.is_empty());
-        
         discovery.start().await.unwrap();
-        sleep(Duration::from_millis(100)).await;
+
+        // Several re-queries happen in this window and none of them should break browsing
+        let events = discovery.drain_events(Duration::from_secs(1)).await;
+        assert!(!events.iter().any(|event| matches!(event, PeerEvent::Error(_))));
+        assert!(discovery.is_running().await);
+
+        discovery.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requery_keeps_peers_fresh() {
+        let backend = Arc::new(crate::StaticBackend::new(vec![loopback_peer(9000, "1")]));
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-requery-fresh")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend)
+            .query_interval(Some(Duration::from_millis(200)))
+            .build()).unwrap();
+        let mut receiver = discovery.subscribe();
+        discovery.start().await.unwrap();
+        while !matches!(receiver.recv().await.unwrap(), PeerEvent::PeerDiscovered(_)) {}
+
+        // Age the peer, the next answered re-query makes it fresh again
+        discovery.discovered_peers.write().await.get_mut("loopback-peer").unwrap().last_seen = SystemTime::UNIX_EPOCH;
+        assert_eq!(discovery.get_peers().await[0].freshness(), Freshness::Stale);
+        sleep(Duration::from_millis(500)).await;
+        assert_eq!(discovery.get_peers().await[0].freshness(), Freshness::Fresh);
+
+        discovery.stop().await.unwrap();
+    }
+
+    /// `StaticBackend` whose name changes are sent by the test
+    #[derive(Debug, Default)]
+    struct RenamingBackend {
+        inner: crate::StaticBackend,
+        renames: Mutex<Option<mpsc::UnboundedSender<(String, String)>>>,
+    }
+
+    impl DiscoveryBackend for RenamingBackend {
+        fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+            self.inner.register(info)
+        }
+
+        fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.unregister(fullname)
+        }
+
+        fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+            self.inner.browse(service_type)
+        }
+
+        fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError> {
+            self.inner.stop_browse(service_type)
+        }
+
+        fn name_changes(&self) -> Result<Option<mpsc::UnboundedReceiver<(String, String)>>, PeerDiscoveryError> {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            *self.renames.lock().unwrap() = Some(sender);
+            Ok(Some(receiver))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_name_conflict_is_adopted() {
+        let backend = Arc::new(RenamingBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-conflict")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+
+        // Another device already answers for our name, so the daemon renamed us
+        let original = discovery.local_fullname();
+        let renamed = "qopyapp-test-conflict (2)._qopyapp._tcp.local.".to_string();
+        let renames = backend.renames.lock().unwrap().clone().unwrap();
+        renames.send((original, renamed.clone())).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while discovery.service_name() != "qopyapp-test-conflict (2)" {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        assert_eq!(discovery.local_hostname(), "qopyapp-test-conflict-2.local.");
+
+        // Re-announcing keeps the new name instead of fighting over the old one
+        discovery.announce().await.unwrap();
+        assert!(backend.inner.registered().contains(&renamed));
+
         discovery.stop().await.unwrap();
+        assert!(!claimed_service_names().lock().unwrap().contains(&renamed));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_service_names_get_suffixed() {
+        let first = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-duplicate")
+            .instance_id("test-duplicate-1")
+            .build()).unwrap();
+        let second = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-duplicate")
+            .instance_id("test-duplicate-2")
+            .build()).unwrap();
+
+        first.start().await.unwrap();
+        second.start().await.unwrap();
+
+        assert_eq!(first.service_name(), "qopyapp-test-duplicate");
+        assert_ne!(second.service_name(), first.service_name());
+        assert!(second.service_name().starts_with("qopyapp-test-duplicate-"));
+        assert_eq!(
+            second.local_peer().await.unwrap().name,
+            format!("{}._qopyapp._tcp.local.", second.service_name())
+        );
+
+        second.stop().await.unwrap();
+        first.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_properties() {
+        let advertiser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-props")
+            .instance_id("test-props")
+            .property("busy", "false")
+            .build()).unwrap();
+        let mut receiver = advertiser.subscribe_unbounded();
+        advertiser.start().await.unwrap();
+
+        let mut update = HashMap::new();
+        update.insert("busy".to_string(), "true".to_string());
+        advertiser.update_properties(update).await.unwrap();
+
+        let changed = loop {
+            if let Some(PeerEvent::LocalPropertiesChanged(properties)) = receiver.recv().await {
+                break properties;
+            }
+        };
+        assert_eq!(changed.get("busy"), Some(&"true".to_string()));
+
+        // A fresh browser resolves the updated record
+        let browser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-props-browser")
+            .instance_id("test-props-browser")
+            .build()).unwrap();
+        let peer = browser.resolve("qopyapp-test-props", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(peer.properties.get("busy"), Some(&"true".to_string()));
+
+        browser.stop().await.unwrap();
+        advertiser.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_announce() {
+        let advertiser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-announce")
+            .instance_id("test-announce")
+            .property("status", "idle")
+            .build()).unwrap();
+        assert!(matches!(advertiser.announce().await, Err(PeerDiscoveryError::ServiceRegistrationFailed(_))));
+        advertiser.start().await.unwrap();
+
+        // Change the properties behind the service's back, only announce() publishes them
+        advertiser.properties.write().await.insert("status".to_string(), "sharing".to_string());
+        advertiser.announce().await.unwrap();
+
+        let browser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-announce-browser")
+            .instance_id("test-announce-browser")
+            .build()).unwrap();
+        let peer = browser.resolve("qopyapp-test-announce", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(peer.properties.get("status"), Some(&"sharing".to_string()));
+
+        browser.stop().await.unwrap();
+        advertiser.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_device_profile_round_trip() {
+        let profile = DeviceProfile {
+            version: "1.2.0".to_string(),
+            device_type: DeviceType::Laptop,
+            capabilities: vec!["clipboard".to_string(), "files".to_string()],
+            extra: HashMap::from([("owner".to_string(), "sam".to_string())]),
+        };
+
+        let properties = profile.to_properties();
+        assert_eq!(properties[VERSION_PROPERTY], "1.2.0");
+        assert_eq!(properties[DEVICE_TYPE_PROPERTY], "laptop");
+        assert_eq!(properties[CAPABILITIES_PROPERTY], "clipboard,files");
+        assert_eq!(DeviceProfile::from_properties(&properties), profile);
+
+        // Bare properties come back with empty defaults
+        let bare = DeviceProfile::from_properties(&HashMap::new());
+        assert_eq!(bare.version, "");
+        assert_eq!(bare.device_type, DeviceType::Unknown("unknown".to_string()));
+        assert!(bare.capabilities.is_empty());
+        assert_eq!(DeviceProfile::from_properties(&bare.to_properties()), bare);
+    }
+
+    #[tokio::test]
+    async fn test_profile_populates_advertised_properties() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .profile(DeviceProfile {
+                version: "2.0".to_string(),
+                device_type: DeviceType::Phone,
+                capabilities: vec!["clipboard".to_string()],
+                extra: HashMap::new(),
+            })
+            .property(VERSION_PROPERTY, "2.1")
+            .build()).unwrap();
+
+        let advertised = discovery.advertised_properties().await;
+        assert_eq!(advertised[DEVICE_TYPE_PROPERTY], "phone");
+        assert_eq!(advertised[CAPABILITIES_PROPERTY], "clipboard");
+        // Explicit properties take precedence over the profile
+        assert_eq!(advertised[VERSION_PROPERTY], "2.1");
+
+        let mut peer = test_peer("phone", "192.168.1.10");
+        peer.properties = advertised;
+        assert_eq!(peer.profile().device_type, DeviceType::Phone);
+    }
+
+    #[tokio::test]
+    async fn test_stop_unregisters_service() {
+        let advertiser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-unregister")
+            .instance_id("test-unregister")
+            .build()).unwrap();
+        advertiser.start().await.unwrap();
+
+        let browser = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-unregister-browser")
+            .instance_id("test-unregister-browser")
+            .build()).unwrap();
+        let mut receiver = browser.subscribe();
+        browser.resolve("qopyapp-test-unregister", Duration::from_secs(10)).await.unwrap();
+
+        // The goodbye for our registered fullname makes the browser drop us
+        advertiser.stop().await.unwrap();
+        let lost = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(PeerEvent::PeerLost(peer)) = receiver.recv().await {
+                    if peer.id == "test-unregister" {
+                        break;
+                    }
+                }
+            }
+        }).await;
+        assert!(lost.is_ok());
+        assert!(browser.get_peer_by_id("test-unregister").await.is_none());
+
+        browser.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drop_ends_background_tasks() {
+        let discovery = PeerDiscovery::new(DiscoveryConfig {
+            service_name: "qopyapp-test-drop".to_string(),
+            instance_id: "test-drop".to_string(),
+            ..DiscoveryConfig::default()
+        }).unwrap();
+        let mut receiver = discovery.subscribe();
+        discovery.start().await.unwrap();
+
+        drop(discovery);
+
+        // The channel only closes once every task holding a copy of the sender is gone
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Err(broadcast::error::RecvError::Closed) = receiver.recv().await {
+                    return;
+                }
+            }
+        }).await;
+        assert!(closed.is_ok(), "background tasks outlived the dropped discovery");
+    }
+
+    #[tokio::test]
+    async fn test_drop_shuts_transport_down() {
+        let backend = Arc::new(CountingBackend::default());
+        let discovery = PeerDiscovery::new(DiscoveryConfig::builder()
+            .service_name("qopyapp-test-drop-transport")
+            .bind_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .backend(backend.clone())
+            .build()).unwrap();
+        discovery.start().await.unwrap();
+
+        drop(discovery);
+
+        let shut_down = tokio::time::timeout(Duration::from_secs(5), async {
+            while backend.shutdowns.load(Ordering::SeqCst) == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(shut_down.is_ok(), "dropping the discovery left the transport running");
+    }
+
+    #[tokio::test]
+    async fn test_peer_discovery_advertises_properties() {
+        let mut properties = HashMap::new();
+        properties.insert("device_type".to_string(), "laptop".to_string());
+
+        let advertiser = PeerDiscovery::new(DiscoveryConfig {
+            service_name: "qopyapp-test-advertiser".to_string(),
+            instance_id: "test-advertiser".to_string(),
+            properties,
+            ..DiscoveryConfig::default()
+        }).unwrap();
+        let browser = PeerDiscovery::new(DiscoveryConfig {
+            service_name: "qopyapp-test-browser".to_string(),
+            instance_id: "test-browser".to_string(),
+            ..DiscoveryConfig::default()
+        }).unwrap();
+
+        let mut receiver = browser.subscribe();
+        browser.start().await.unwrap();
+        advertiser.start().await.unwrap();
+
+        // Wait for the advertiser to be resolved by the browser
+        let peer = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(PeerEvent::PeerDiscovered(peer)) = receiver.recv().await {
+                    if peer.name.starts_with("qopyapp-test-advertiser") {
+                        return peer;
+                    }
+                }
+            }
+        }).await.expect("advertiser was not discovered");
+
+        assert_eq!(peer.properties.get("device_type"), Some(&"laptop".to_string()));
+
+        advertiser.stop().await.unwrap();
+        browser.stop().await.unwrap();
     }
 }
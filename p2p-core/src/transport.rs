@@ -0,0 +1,263 @@
+use crate::error::PeerDiscoveryError;
+use crate::peer_discovery::{local_interfaces, multicast_available, DiscoveryConfig, NetworkInterface};
+use async_trait::async_trait;
+use mdns_sd::{DaemonEvent, IfKind, RRType, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// Where registrations go and browse events come from: the mDNS daemon, the in-memory
+/// backend of `DiscoveryConfig::test_mode`, or whatever is set as `DiscoveryConfig::backend`
+///
+/// Browse events use the mdns-sd types so `PeerDiscovery` handles them like any resolved service.
+#[async_trait]
+pub trait DiscoveryBackend: fmt::Debug + Send + Sync {
+    fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError>;
+
+    fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError>;
+
+    /// Start delivering events for `service_type`, replacing any earlier browse of that type.
+    /// The browse ends when the sender is dropped.
+    fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError>;
+
+    fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError>;
+
+    /// Renames the backend makes to our registrations when the network already uses their name,
+    /// as `(original, renamed)` fullnames. Backends that never see conflicts return `None`.
+    fn name_changes(&self) -> Result<Option<mpsc::UnboundedReceiver<(String, String)>>, PeerDiscoveryError> {
+        Ok(None)
+    }
+
+    /// Deliver `event` to the browse of its service type as if the network had, returns whether
+    /// one received it. Backends fed only by the network ignore it.
+    fn inject(&self, _event: ServiceEvent) -> bool {
+        false
+    }
+
+    /// Whether peers can be reached through this backend, `start()` runs manual-only when not
+    fn multicast_available(&self) -> bool {
+        true
+    }
+
+    /// Release the backend once discovery stops. Does nothing by default, since a backend
+    /// passed in through `DiscoveryConfig::backend` may be shared.
+    async fn shutdown(&self) -> Result<(), PeerDiscoveryError> {
+        Ok(())
+    }
+}
+
+/// The backend `config` asks for, mDNS unless it sets `backend` or `test_mode`
+pub(crate) fn new_backend(config: &DiscoveryConfig) -> Result<Arc<dyn DiscoveryBackend>, PeerDiscoveryError> {
+    if let Some(backend) = &config.backend {
+        return Ok(backend.clone());
+    }
+    if config.test_mode {
+        return Ok(Arc::new(StaticBackend::default()));
+    }
+
+    let daemon = start_daemon(ServiceDaemon::new)?;
+    // Multicast only over the chosen interfaces when any are given
+    if !config.multicast_interfaces.is_empty() {
+        daemon.disable_interface(IfKind::All)?;
+        for ip in &config.multicast_interfaces {
+            daemon.enable_interface(IfKind::Addr(*ip))?;
+        }
+    }
+    Ok(Arc::new(MdnsBackend {
+        daemon,
+        multicast_interfaces: config.multicast_interfaces.clone(),
+        forwarders: Mutex::new(HashMap::new()),
+        monitor: Mutex::new(None),
+    }))
+}
+
+/// Create the daemon, reporting failure apart from later mDNS errors since it's usually a socket or permission problem
+pub(crate) fn start_daemon(
+    create: impl FnOnce() -> Result<ServiceDaemon, mdns_sd::Error>,
+) -> Result<ServiceDaemon, PeerDiscoveryError> {
+    create().map_err(|e| PeerDiscoveryError::DaemonInitFailed(e.to_string()))
+}
+
+/// The real mDNS daemon
+pub(crate) struct MdnsBackend {
+    daemon: ServiceDaemon,
+    // Empty when the daemon uses every interface
+    multicast_interfaces: Vec<IpAddr>,
+    // Tasks moving daemon events onto each browse's channel, by service type
+    forwarders: Mutex<HashMap<String, AbortHandle>>,
+    // Task moving the daemon's name changes onto the `name_changes` channel
+    monitor: Mutex<Option<AbortHandle>>,
+}
+
+impl fmt::Debug for MdnsBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MdnsBackend")
+            .field("multicast_interfaces", &self.multicast_interfaces)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl DiscoveryBackend for MdnsBackend {
+    fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+        Ok(self.daemon.register(info)?)
+    }
+
+    fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError> {
+        self.daemon.unregister(fullname)?;
+        Ok(())
+    }
+
+    fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+        let events = self.daemon.browse(service_type)?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // The daemon closes its channel when the browse is stopped or replaced, which ends ours too
+        let forwarder = tokio::spawn(async move {
+            while let Ok(event) = events.recv_async().await {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        let replaced = self.forwarders.lock().unwrap()
+            .insert(service_type.to_string(), forwarder.abort_handle());
+        if let Some(replaced) = replaced {
+            replaced.abort();
+        }
+        Ok(receiver)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError> {
+        if let Some(forwarder) = self.forwarders.lock().unwrap().remove(service_type) {
+            forwarder.abort();
+        }
+        Ok(self.daemon.stop_browse(service_type)?)
+    }
+
+    /// The daemon probes every name it registers and appends " (2)" and up when another device answers
+    fn name_changes(&self) -> Result<Option<mpsc::UnboundedReceiver<(String, String)>>, PeerDiscoveryError> {
+        let events = self.daemon.monitor()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let forwarder = tokio::spawn(async move {
+            while let Ok(event) = events.recv_async().await {
+                // The instance name is carried by the SRV record, A and AAAA renames are about the host
+                let DaemonEvent::NameChange(change) = event else {
+                    continue;
+                };
+                if change.rr_type == RRType::SRV && sender.send((change.original, change.new_name)).is_err() {
+                    break;
+                }
+            }
+        });
+        if let Some(replaced) = self.monitor.lock().unwrap().replace(forwarder.abort_handle()) {
+            replaced.abort();
+        }
+        Ok(Some(receiver))
+    }
+
+    fn multicast_available(&self) -> bool {
+        match local_interfaces() {
+            Ok(interfaces) => {
+                let chosen: Vec<NetworkInterface> = interfaces.into_iter()
+                    .filter(|interface| {
+                        self.multicast_interfaces.is_empty() || self.multicast_interfaces.contains(&interface.ip)
+                    })
+                    .collect();
+                multicast_available(&chosen)
+            }
+            // Can't tell, so let the daemon try
+            Err(_) => true,
+        }
+    }
+
+    /// Stop the forwarders and the daemon, then wait for its threads to exit
+    async fn shutdown(&self) -> Result<(), PeerDiscoveryError> {
+        for (_, forwarder) in self.forwarders.lock().unwrap().drain() {
+            forwarder.abort();
+        }
+        if let Some(monitor) = self.monitor.lock().unwrap().take() {
+            monitor.abort();
+        }
+        let status = self.daemon.shutdown()?;
+        let _ = status.recv_async().await;
+        Ok(())
+    }
+}
+
+/// Backend over a fixed list of services, e.g. peers configured by hand or fixtures in tests
+///
+/// Every browse resolves the listed services of its type right away and then receives whatever
+/// is injected; our own registrations are only recorded, see `registered`. Nothing reaches the
+/// network, an empty one is what `DiscoveryConfig::test_mode` uses.
+#[derive(Debug, Default)]
+pub struct StaticBackend {
+    services: Vec<ServiceInfo>,
+    registered: Mutex<Vec<String>>,
+    browsers: Mutex<HashMap<String, mpsc::UnboundedSender<ServiceEvent>>>,
+}
+
+impl StaticBackend {
+    pub fn new(services: Vec<ServiceInfo>) -> Self {
+        StaticBackend {
+            services,
+            ..StaticBackend::default()
+        }
+    }
+
+    /// Fullnames currently registered through this backend
+    pub fn registered(&self) -> Vec<String> {
+        self.registered.lock().unwrap().clone()
+    }
+}
+
+impl DiscoveryBackend for StaticBackend {
+    fn register(&self, info: ServiceInfo) -> Result<(), PeerDiscoveryError> {
+        let fullname = info.get_fullname().to_string();
+        let mut registered = self.registered.lock().unwrap();
+        if !registered.contains(&fullname) {
+            registered.push(fullname);
+        }
+        Ok(())
+    }
+
+    fn unregister(&self, fullname: &str) -> Result<(), PeerDiscoveryError> {
+        self.registered.lock().unwrap().retain(|registered| registered != fullname);
+        Ok(())
+    }
+
+    fn browse(&self, service_type: &str) -> Result<mpsc::UnboundedReceiver<ServiceEvent>, PeerDiscoveryError> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // Subtype browses ("<subtype>._sub.<type>") see every service of the base type
+        for info in self.services.iter().filter(|info| service_type.ends_with(info.get_type())) {
+            let _ = sender.send(ServiceEvent::ServiceResolved(info.clone()));
+        }
+        self.browsers.lock().unwrap().insert(service_type.to_string(), sender);
+        Ok(receiver)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> Result<(), PeerDiscoveryError> {
+        // Dropping the sender ends the browse
+        self.browsers.lock().unwrap().remove(service_type);
+        Ok(())
+    }
+
+    fn inject(&self, event: ServiceEvent) -> bool {
+        let service_type = match &event {
+            ServiceEvent::ServiceResolved(info) => info.get_type().to_string(),
+            ServiceEvent::ServiceRemoved(service_type, _) => service_type.clone(),
+            _ => return false,
+        };
+
+        let browsers = self.browsers.lock().unwrap();
+        // Subtype browses use "<subtype>._sub.<type>", so fall back to any browse ending in the type
+        let browser = browsers.get(&service_type).or_else(|| {
+            browsers.iter()
+                .find(|(browse_type, _)| browse_type.ends_with(&service_type))
+                .map(|(_, browser)| browser)
+        });
+        browser.is_some_and(|browser| browser.send(event).is_ok())
+    }
+}